@@ -0,0 +1,127 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use serde_json::json;
+use tauri::AppHandle;
+use crate::hotkey::HotkeyAction;
+use crate::{audio, handle_stop_recording_workflow, start_session, take_pending_job, ProcessingJob};
+
+// Local HTTP API for scripts that want a transcript back rather than typed into a window:
+//
+//   POST /record/start  starts recording
+//   POST /record/stop   stops recording and responds with {"text": ...} once transcribed
+//
+// Every request needs an `Authorization: Bearer <api_token>` header.
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("Empty request")?.to_string();
+    let path = parts.next().ok_or("Missing request path")?.to_string();
+
+    // Bodies are never needed, so reading stops at the end of the headers.
+    let mut token = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                token = value.trim().strip_prefix("Bearer ").map(|t| t.trim().to_string());
+            }
+        }
+    }
+    Ok(Request { method, path, token })
+}
+
+// Compares every byte so the response time doesn't reveal how much of a guess was right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn respond(mut stream: TcpStream, status: &str, body: serde_json::Value) {
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn stop_and_transcribe(app: &AppHandle) -> Result<String, String> {
+    if !audio::is_recording() {
+        return Err("Not recording".to_string());
+    }
+    let (reply_tx, reply_rx) = mpsc::channel();
+    let job = ProcessingJob {
+        reply: Some(reply_tx),
+        ..take_pending_job()
+    };
+    handle_stop_recording_workflow(app, None, job)?;
+    reply_rx.recv().map_err(|_| "Recording was dropped before it was transcribed".to_string())?
+}
+
+fn handle_connection(app: &AppHandle, stream: TcpStream, token: &str) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => return respond(stream, "400 Bad Request", json!({ "error": e })),
+    };
+    if !request.token.as_deref().is_some_and(|given| tokens_match(given, token)) {
+        return respond(stream, "401 Unauthorized", json!({ "error": "Missing or invalid token" }));
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/record/start") => match start_session(ProcessingJob::new(HotkeyAction::default())) {
+            Ok(()) => respond(stream, "200 OK", json!({ "recording": true })),
+            Err(e) => respond(stream, "409 Conflict", json!({ "error": e })),
+        },
+        ("POST", "/record/stop") => match stop_and_transcribe(app) {
+            Ok(text) => respond(stream, "200 OK", json!({ "text": text })),
+            Err(e) => respond(stream, "409 Conflict", json!({ "error": e })),
+        },
+        _ => respond(stream, "404 Not Found", json!({ "error": "Unknown endpoint" })),
+    }
+}
+
+// Only listens on the loopback interface.
+pub fn start(app: AppHandle, port: u16, token: String) {
+    if token.trim().is_empty() {
+        eprintln!("Local API is enabled but api_token is empty; not starting it");
+        return;
+    }
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to start local API on port {}: {}", port, e);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let app = app.clone();
+            let token = token.clone();
+            // Stop requests block until transcription finishes, so each gets its own thread.
+            thread::spawn(move || handle_connection(&app, stream, &token));
+        }
+    });
+}
@@ -1,48 +1,222 @@
 use std::thread;
 use std::time::Duration;
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::Instant;
+use std::f64::consts::PI;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use tauri::{AppHandle, Emitter};
+use serde::Serialize;
 use serde_json::json;
 use hound::{WavWriter, WavSpec};
+use crate::history::AudioStats;
+use crate::settings::{InputSource, DEFAULT_VOLUME_THRESHOLD};
 
 const TARGET_SAMPLE_RATE: u32 = 16000;
+const CLIPPING_THRESHOLD: f32 = 0.999;
+// How often the pill is told how long the current recording has been running.
+const PROGRESS_INTERVAL_MS: u64 = 250;
+const DURATION_DRIFT_WARN_MS: u64 = 500;
+// Zero crossings of the sinc kernel on each side of its centre.
+const RESAMPLE_ZERO_CROSSINGS: f64 = 8.0;
+// Kernel table entries per input sample; offsets in between are interpolated linearly.
+const KERNEL_TABLE_RESOLUTION: f64 = 512.0;
+// Longest pre-roll that can be requested; the idle ring buffer holds this much audio.
+const MAX_PREROLL_MS: u64 = 1000;
+const MAX_PREROLL_SAMPLES: usize = (TARGET_SAMPLE_RATE as u64 * MAX_PREROLL_MS / 1000) as usize;
+
+// Full-scale value of an integer PCM sample, e.g. 32767 for 16-bit.
+pub fn sample_scale(bits_per_sample: u16) -> f32 {
+    ((1i64 << (bits_per_sample - 1)) - 1) as f32
+}
+
+// Updated from the capture callback so diagnostics can tell whether audio is flowing.
+static CHUNKS_RECEIVED: AtomicU64 = AtomicU64::new(0);
+static LAST_INPUT_LEVEL: AtomicU32 = AtomicU32::new(0);
+// Linear input gain as f32 bits (starting at 1.0) so it can change without reopening the stream.
+static INPUT_GAIN: AtomicU32 = AtomicU32::new(0x3f80_0000);
+// While set, every captured chunk's level is sent to the dashboard as `mic-level`.
+static MIC_TEST: AtomicBool = AtomicBool::new(false);
+// Set from the stream's error callback when the device goes away, e.g. a USB mic is unplugged.
+static DEVICE_LOST: AtomicBool = AtomicBool::new(false);
+
+// Windowed-sinc resampler. The kernel low-pass filters below the output Nyquist
+// so content above 8 kHz doesn't alias back into the speech band when decimating.
+struct Resampler {
+    // Input samples per output sample.
+    ratio: f64,
+    half_width: f64,
+    // The kernel sampled every 1/KERNEL_TABLE_RESOLUTION input samples from its centre
+    // outwards, built once so the capture callback does no trigonometry.
+    table: Vec<f64>,
+    input: Vec<f32>,
+    // Where the next output sample falls in `input`.
+    position: f64,
+}
+
+impl Resampler {
+    fn new(ratio: f64) -> Self {
+        // A little under Nyquist to leave room for the filter's transition band.
+        let cutoff = 0.45 * ratio.recip().min(1.0);
+        let half_width = RESAMPLE_ZERO_CROSSINGS / (2.0 * cutoff);
+        let len = (half_width * KERNEL_TABLE_RESOLUTION).ceil() as usize + 2;
+        let table = (0..len)
+            .map(|i| Self::kernel(cutoff, half_width, i as f64 / KERNEL_TABLE_RESOLUTION))
+            .collect();
+        Self {
+            ratio,
+            half_width,
+            table,
+            input: Vec::new(),
+            position: 0.0,
+        }
+    }
+
+    // `cutoff` is a fraction of the input sample rate.
+    fn kernel(cutoff: f64, half_width: f64, x: f64) -> f64 {
+        if x.abs() >= half_width {
+            return 0.0;
+        }
+        let arg = PI * 2.0 * cutoff * x;
+        let sinc = if arg == 0.0 { 1.0 } else { arg.sin() / arg };
+        let phase = PI * x / half_width;
+        let blackman = 0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos();
+        2.0 * cutoff * sinc * blackman
+    }
+
+    fn kernel_at(&self, x: f64) -> f64 {
+        let t = x.abs() * KERNEL_TABLE_RESOLUTION;
+        let index = t as usize;
+        if index + 1 >= self.table.len() {
+            return 0.0;
+        }
+        let frac = t - index as f64;
+        self.table[index] + (self.table[index + 1] - self.table[index]) * frac
+    }
+
+    // Carries the kernel's tail over between chunks so there are no seams at chunk boundaries.
+    fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.input.extend_from_slice(samples);
+        let mut output = Vec::new();
+        while self.position + self.half_width < self.input.len() as f64 {
+            let first = (self.position - self.half_width).ceil().max(0.0) as usize;
+            let last = (self.position + self.half_width).floor() as usize;
+            let sample: f64 = (first..=last)
+                .map(|k| self.input[k] as f64 * self.kernel_at(self.position - k as f64))
+                .sum();
+            output.push(sample as f32);
+            self.position += self.ratio;
+        }
+        let consumed = ((self.position - self.half_width).floor().max(0.0) as usize).min(self.input.len());
+        self.input.drain(..consumed);
+        self.position -= consumed as f64;
+        output
+    }
+}
 
 pub struct AudioProcessor {
-    buffer: Arc<Mutex<Vec<f32>>>,
     wav_writer: Option<WavWriter<std::io::BufWriter<std::fs::File>>>,
+    sample_scale: f32,
     is_recording: Arc<Mutex<bool>>,
-    downsample_ratio: f32,
+    // None when the device already delivers 16 kHz.
+    resampler: Option<Resampler>,
+    peak_level: f32,
+    sum_squares: f64,
+    clipped_samples: u64,
+    samples_written: u64,
+    // Chunks quieter than this RMS level are counted as silence.
+    volume_threshold: f32,
+    speech_chunks: u64,
+    total_chunks: u64,
+    speech_samples: u64,
+    trailing_silence_samples: u64,
+    last_progress_ms: u64,
+    recording_started: Option<Instant>,
+    last_stats: Option<AudioStats>,
+    // Audio captured while idle, so a recording can start slightly before the hotkey.
+    recent: VecDeque<f32>,
+    // Samples after this many are dropped; 0 means no limit.
+    max_samples: u64,
+    limit_notified: bool,
 }
 
 impl AudioProcessor {
     pub fn new(original_sample_rate: u32) -> Self {
-        let downsample_ratio = if original_sample_rate != TARGET_SAMPLE_RATE {
-            original_sample_rate as f32 / TARGET_SAMPLE_RATE as f32
-        } else {
-            1.0
-        };
+        let resampler = Self::resampler_for(original_sample_rate);
 
         Self {
-            buffer: Arc::new(Mutex::new(Vec::new())),
             wav_writer: None,
+            sample_scale: sample_scale(16),
             is_recording: Arc::new(Mutex::new(false)),
-            downsample_ratio,
+            resampler,
+            peak_level: 0.0,
+            sum_squares: 0.0,
+            clipped_samples: 0,
+            samples_written: 0,
+            volume_threshold: DEFAULT_VOLUME_THRESHOLD,
+            speech_chunks: 0,
+            total_chunks: 0,
+            speech_samples: 0,
+            trailing_silence_samples: 0,
+            last_progress_ms: 0,
+            recording_started: None,
+            last_stats: None,
+            recent: VecDeque::new(),
+            max_samples: 0,
+            limit_notified: false,
         }
     }
 
-    pub fn start_recording(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    fn resampler_for(sample_rate: u32) -> Option<Resampler> {
+        (sample_rate != TARGET_SAMPLE_RATE).then(|| Resampler::new(sample_rate as f64 / TARGET_SAMPLE_RATE as f64))
+    }
+
+    // Used when capture moves to another device partway through a recording.
+    fn set_input_rate(&mut self, sample_rate: u32) {
+        self.resampler = Self::resampler_for(sample_rate);
+    }
+
+    // The last `preroll_ms` of idle audio is written first so the opening syllable isn't
+    // lost to the time it takes to press the key and start speaking. Audio beyond
+    // `max_recording_secs` (0 for unlimited) is dropped to stay under upload size limits;
+    // record_chunk ends the session there.
+    pub fn start_recording(&mut self, bits_per_sample: u16, preroll_ms: u64, max_recording_secs: u64, volume_threshold: f32) -> Result<(), Box<dyn std::error::Error>> {
+        let filename = std::env::temp_dir().join("vwisper_audio_latest.wav");
+        self.start_recording_to(&filename, bits_per_sample, preroll_ms, max_recording_secs, volume_threshold)
+    }
+
+    fn start_recording_to(&mut self, filename: &std::path::Path, bits_per_sample: u16, preroll_ms: u64, max_recording_secs: u64, volume_threshold: f32) -> Result<(), Box<dyn std::error::Error>> {
         let spec = WavSpec {
             channels: 1,
             sample_rate: TARGET_SAMPLE_RATE,
-            bits_per_sample: 16,
+            bits_per_sample,
             sample_format: hound::SampleFormat::Int,
         };
 
-        let temp_dir = std::env::temp_dir();
-        let filename = temp_dir.join("vwisper_audio_latest.wav");
-        
-        self.wav_writer = Some(WavWriter::create(&filename, spec)?);
+        self.wav_writer = Some(WavWriter::create(filename, spec)?);
+        self.sample_scale = sample_scale(bits_per_sample);
+        self.peak_level = 0.0;
+        self.sum_squares = 0.0;
+        self.clipped_samples = 0;
+        self.samples_written = 0;
+        self.volume_threshold = volume_threshold;
+        log::debug!("Counting chunks below RMS {} as silence", volume_threshold);
+        self.speech_chunks = 0;
+        self.total_chunks = 0;
+        self.speech_samples = 0;
+        self.trailing_silence_samples = 0;
+        self.last_progress_ms = 0;
+        self.last_stats = None;
+        self.max_samples = TARGET_SAMPLE_RATE as u64 * max_recording_secs;
+        self.limit_notified = false;
+        let preroll_len = ((TARGET_SAMPLE_RATE as u64 * preroll_ms.min(MAX_PREROLL_MS) / 1000) as usize).min(self.recent.len());
+        let preroll: Vec<f32> = self.recent.drain(self.recent.len() - preroll_len..).collect();
+        self.recent.clear();
+        let preroll_duration = Duration::from_millis(preroll_len as u64 * 1000 / TARGET_SAMPLE_RATE as u64);
+        let now = Instant::now();
+        self.recording_started = Some(now.checked_sub(preroll_duration).unwrap_or(now));
+        self.write_samples(&preroll)?;
         *self.is_recording.lock().unwrap() = true;
         Ok(())
     }
@@ -51,40 +225,102 @@ impl AudioProcessor {
         if let Some(writer) = self.wav_writer.take() {
             writer.finalize()?;
             *self.is_recording.lock().unwrap() = false;
+            let stats = self.current_stats();
+            // The two only differ by buffering latency; a large gap means samples were
+            // dropped or the resampler/channel handling is off.
+            if let Some(wall_clock_ms) = stats.wall_clock_ms.filter(|_| !self.limit_reached()) {
+                if wall_clock_ms.abs_diff(stats.duration_ms) > DURATION_DRIFT_WARN_MS {
+                    eprintln!(
+                        "Recording length mismatch: {}ms of audio over {}ms of recording",
+                        stats.duration_ms, wall_clock_ms
+                    );
+                }
+            }
+            self.last_stats = Some(stats);
         }
         Ok(())
     }
 
-    pub fn process_audio(&mut self, samples: &[f32]) -> Vec<f32> {
-        let mut buffer = self.buffer.lock().unwrap();
-        buffer.extend_from_slice(samples);
-
-        if self.downsample_ratio != 1.0 {
-            let mut downsampled = Vec::new();
-            let mut index = 0.0;
-            
-            while index < buffer.len() as f32 {
-                let sample_index = index as usize;
-                if sample_index < buffer.len() {
-                    downsampled.push(buffer[sample_index]);
-                }
-                index += self.downsample_ratio;
-            }
-            
-            buffer.clear();
-            downsampled
+    fn current_stats(&self) -> AudioStats {
+        let rms_level = if self.samples_written > 0 {
+            (self.sum_squares / self.samples_written as f64).sqrt() as f32
         } else {
-            let output = buffer.clone();
-            buffer.clear();
-            output
+            0.0
+        };
+        AudioStats {
+            peak_level: self.peak_level,
+            rms_level,
+            clipped_samples: self.clipped_samples,
+            duration_ms: self.elapsed_ms(),
+            speech_ratio: if self.total_chunks > 0 {
+                Some(self.speech_chunks as f32 / self.total_chunks as f32)
+            } else {
+                None
+            },
+            wall_clock_ms: self.recording_started.map(|start| start.elapsed().as_millis() as u64),
+        }
+    }
+
+    pub fn last_stats(&self) -> Option<AudioStats> {
+        self.last_stats.clone()
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.samples_written * 1000 / TARGET_SAMPLE_RATE as u64
+    }
+
+    // Returns the elapsed recording time once every PROGRESS_INTERVAL_MS.
+    fn progress_due(&mut self) -> Option<u64> {
+        let elapsed = self.elapsed_ms();
+        if elapsed < self.last_progress_ms + PROGRESS_INTERVAL_MS {
+            return None;
+        }
+        self.last_progress_ms = elapsed;
+        Some(elapsed)
+    }
+
+    // Milliseconds of speech so far and of silence since it last stopped.
+    pub fn voice_activity(&self) -> (u64, u64) {
+        let to_ms = |samples: u64| samples * 1000 / TARGET_SAMPLE_RATE as u64;
+        (to_ms(self.speech_samples), to_ms(self.trailing_silence_samples))
+    }
+
+    pub fn process_audio(&mut self, samples: &[f32]) -> Vec<f32> {
+        match &mut self.resampler {
+            Some(resampler) => resampler.process(samples),
+            None => samples.to_vec(),
         }
     }
 
     pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), Box<dyn std::error::Error>> {
+        let samples = if self.max_samples > 0 {
+            let remaining = self.max_samples.saturating_sub(self.samples_written) as usize;
+            &samples[..samples.len().min(remaining)]
+        } else {
+            samples
+        };
         if let Some(writer) = &mut self.wav_writer {
+            if !samples.is_empty() {
+                let rms = (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+                self.total_chunks += 1;
+                if rms >= self.volume_threshold {
+                    self.speech_chunks += 1;
+                    self.speech_samples += samples.len() as u64;
+                    self.trailing_silence_samples = 0;
+                } else {
+                    self.trailing_silence_samples += samples.len() as u64;
+                }
+            }
             for &sample in samples {
-                let sample_i16 = (sample * i16::MAX as f32) as i16;
-                writer.write_sample(sample_i16)?;
+                let level = sample.abs();
+                self.peak_level = self.peak_level.max(level);
+                self.sum_squares += (sample as f64) * (sample as f64);
+                if level >= CLIPPING_THRESHOLD {
+                    self.clipped_samples += 1;
+                }
+                self.samples_written += 1;
+                // hound rejects values outside the bit depth, and gain or loopback can exceed 1.0.
+                writer.write_sample((sample.clamp(-1.0, 1.0) * self.sample_scale) as i32)?;
             }
         }
         Ok(())
@@ -93,6 +329,98 @@ impl AudioProcessor {
     pub fn is_recording(&self) -> bool {
         *self.is_recording.lock().unwrap()
     }
+
+    fn limit_reached(&self) -> bool {
+        self.max_samples > 0 && self.samples_written >= self.max_samples
+    }
+
+    fn remember(&mut self, samples: &[f32]) {
+        self.recent.extend(samples);
+        let excess = self.recent.len().saturating_sub(MAX_PREROLL_SAMPLES);
+        self.recent.drain(..excess);
+    }
+}
+
+// Turns raw capture chunks into bar heights for the pill's waveform.
+struct Visualizer {
+    bar_count: usize,
+    gain: f32,
+    auto_gain: bool,
+    // Decaying peak of recent chunk levels, used to scale the bars when auto-gain is on.
+    recent_peak: f32,
+}
+
+// Per-chunk decay of the auto-gain peak, roughly halving it every second of audio.
+const AUTO_GAIN_DECAY: f32 = 0.98;
+// Keeps auto-gain from amplifying background noise into full-height bars.
+const AUTO_GAIN_FLOOR: f32 = 0.02;
+
+impl Visualizer {
+    fn from_settings(settings: &crate::settings::Settings) -> Self {
+        Self {
+            bar_count: settings.viz_bar_count.max(1),
+            gain: settings.viz_sensitivity,
+            auto_gain: settings.viz_auto_gain,
+            recent_peak: AUTO_GAIN_FLOOR,
+        }
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+        (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    // Returns one level per bar plus the overall volume, all in 0..=1.
+    fn levels(&mut self, samples: &[f32]) -> (Vec<f32>, f32) {
+        let rms = Self::rms(samples);
+        let gain = if self.auto_gain {
+            self.recent_peak = (self.recent_peak * AUTO_GAIN_DECAY).max(rms).max(AUTO_GAIN_FLOOR);
+            1.0 / self.recent_peak
+        } else {
+            self.gain
+        };
+        let chunk_len = samples.len().div_ceil(self.bar_count).max(1);
+        let mut bars: Vec<f32> = samples
+            .chunks(chunk_len)
+            .map(|chunk| (Self::rms(chunk) * gain).min(1.0))
+            .collect();
+        bars.resize(self.bar_count, 0.0);
+        (bars, (rms * gain).min(1.0))
+    }
+}
+
+// The visualization and the recording each get the raw chunk read-only, so tuning the
+// pill's gain can never change what is sent for transcription.
+fn send_visualization(app: &AppHandle, visualizer: &mut Visualizer, samples: &[f32]) {
+    let (bar_values, volume) = visualizer.levels(samples);
+    let _ = app.emit_to("main", "audio-data", json!({
+        "samples": bar_values,
+        "volume": volume
+    }));
+}
+
+fn record_chunk(app: &AppHandle, processor: &mut AudioProcessor, samples: &[f32], rms: f32) {
+    let downsampled = processor.process_audio(samples);
+    if processor.is_recording() && !downsampled.is_empty() {
+        if let Err(e) = processor.write_samples(&downsampled) {
+            eprintln!("Failed to write samples: {}", e);
+        }
+        if let Some(elapsed_ms) = processor.progress_due() {
+            let _ = app.emit_to("main", "recording-progress", json!({
+                "elapsed_ms": elapsed_ms,
+                "level": rms
+            }));
+        }
+        if processor.limit_reached() && !processor.limit_notified {
+            processor.limit_notified = true;
+            let _ = app.emit("recording-max-duration", processor.max_samples / TARGET_SAMPLE_RATE as u64);
+            crate::stop_key_session(app);
+        }
+    } else {
+        processor.remember(&downsampled);
+    }
 }
 
 static AUDIO_PROCESSOR: std::sync::OnceLock<Arc<Mutex<Option<AudioProcessor>>>> = std::sync::OnceLock::new();
@@ -101,80 +429,318 @@ pub fn get_audio_processor() -> Arc<Mutex<Option<AudioProcessor>>> {
     AUDIO_PROCESSOR.get_or_init(|| Arc::new(Mutex::new(None))).clone()
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct CaptureInfo {
+    pub device: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+// Bumped to retire the running capture thread when audio is reinitialized.
+static CAPTURE_GENERATION: AtomicU64 = AtomicU64::new(0);
+const REINIT_TIMEOUT: Duration = Duration::from_secs(3);
+
 pub fn start_audio_capture(app_handle: AppHandle) {
-    thread::spawn(move || {
-        let host = cpal::default_host();
+    spawn_capture(app_handle, None);
+}
 
-        let device = match host.default_input_device() {
-            Some(device) => device,
-            None => {
-                eprintln!("No default input device found");
-                return;
-            }
-        };
+// Re-selects the input device, e.g. after the default changed in the OS, and restarts capture
+// with its current sample rate and channel count.
+pub fn reinit_audio_capture(app_handle: AppHandle) -> Result<CaptureInfo, String> {
+    if is_recording() {
+        return Err("Cannot reinitialize audio while recording".to_string());
+    }
+    let (tx, rx) = std::sync::mpsc::channel();
+    spawn_capture(app_handle, Some(tx));
+    rx.recv_timeout(REINIT_TIMEOUT)
+        .map_err(|_| "Timed out waiting for the audio device".to_string())?
+}
 
-        let config = match device.default_input_config() {
-            Ok(config) => config,
+fn spawn_capture(app_handle: AppHandle, ready: Option<std::sync::mpsc::Sender<Result<CaptureInfo, String>>>) {
+    let generation = CAPTURE_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    thread::spawn(move || {
+        // The stream has to stay on the thread that created it, so it is kept alive here
+        // until a newer capture thread takes over.
+        let result = open_stream(app_handle.clone());
+        let mut stream = match result {
+            Ok((stream, info)) => {
+                if let Some(ready) = ready {
+                    let _ = ready.send(Ok(info));
+                }
+                stream
+            }
             Err(e) => {
-                eprintln!("Failed to get default input config: {}", e);
+                eprintln!("{}", e);
+                if let Some(ready) = ready {
+                    let _ = ready.send(Err(e));
+                }
                 return;
             }
         };
 
-        let original_sample_rate = config.sample_rate().0;
-        let audio_processor = AudioProcessor::new(original_sample_rate);
-        
-        let processor_arc = get_audio_processor();
-        *processor_arc.lock().unwrap() = Some(audio_processor);
-
-        let app_handle_stream = app_handle.clone();
-        let stream = match device.build_input_stream(
-            &config.into(),
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                let samples: Vec<f32> = data.to_vec();
-                
-                let rms = (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
-                let normalized = (rms * 10.0).min(1.0);
-                
-                let bar_values = vec![normalized; 10];
-                let _ = app_handle_stream.emit_to("main", "audio-data", json!({
-                    "samples": bar_values
-                }));
-
-                if let Some(processor) = &mut *processor_arc.lock().unwrap() {
-                    let downsampled = processor.process_audio(&samples);
-                    
-                    if processor.is_recording() && !downsampled.is_empty() {
-                        if let Err(e) = processor.write_samples(&downsampled) {
-                            eprintln!("Failed to write samples: {}", e);
-                        }
+        while CAPTURE_GENERATION.load(Ordering::SeqCst) == generation {
+            if DEVICE_LOST.swap(false, Ordering::SeqCst) {
+                eprintln!("Input device disconnected, reopening audio capture");
+                match recover_capture(|| open_stream(app_handle.clone())) {
+                    Some((new_stream, info)) => {
+                        eprintln!("Audio capture switched to {}", info.device);
+                        stream = new_stream;
+                    }
+                    None => {
+                        device_lost(&app_handle);
+                        return;
                     }
                 }
-            },
-            |err| eprintln!("Audio stream error: {}", err),
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        drop(stream);
+    });
+}
+
+// One attempt per loss; select_input_device falls back to the default device. None means
+// capture can't continue and the loss should be reported.
+fn recover_capture<S>(reopen: impl FnOnce() -> Result<S, String>) -> Option<S> {
+    match reopen() {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            eprintln!("{}", e);
             None
-        ) {
-            Ok(stream) => stream,
+        }
+    }
+}
+
+// Ends the recording so whatever was captured before the device went away can still be
+// transcribed when the hotkey is released.
+fn device_lost(app_handle: &AppHandle) {
+    if is_recording() {
+        if let Err(e) = stop_recording() {
+            eprintln!("Failed to stop recording: {}", e);
+        }
+    }
+    let _ = app_handle.emit("recording-device-lost", ());
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    // From the device's default input config, when it could be queried.
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+// Enumeration fails on machines without an audio server (e.g. headless Linux), which is
+// reported as having no devices rather than as an error.
+pub fn list_input_devices() -> Vec<AudioDeviceInfo> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|device| device.name().ok());
+    let devices = match host.input_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            eprintln!("Failed to enumerate input devices: {}", e);
+            return Vec::new();
+        }
+    };
+    devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let config = device.default_input_config().ok();
+            Some(AudioDeviceInfo {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                sample_rate: config.as_ref().map(|config| config.sample_rate().0),
+                channels: config.as_ref().map(|config| config.channels()),
+                name,
+            })
+        })
+        .collect()
+}
+
+// The device named in settings if it is connected, otherwise the system default, so an
+// unplugged or renamed microphone doesn't stop capture altogether.
+fn select_input_device(host: &cpal::Host, name: Option<&str>) -> Option<cpal::Device> {
+    let devices = host.input_devices().ok().into_iter().flatten().map(|device| (device.name().ok(), device));
+    choose_device(name, devices, || host.default_input_device())
+}
+
+// The selection behind select_input_device, apart from cpal so it can be tested.
+fn choose_device<D>(
+    name: Option<&str>,
+    devices: impl IntoIterator<Item = (Option<String>, D)>,
+    default: impl FnOnce() -> Option<D>,
+) -> Option<D> {
+    if let Some(name) = name {
+        let found = devices
+            .into_iter()
+            .find(|(device_name, _)| device_name.as_deref() == Some(name))
+            .map(|(_, device)| device);
+        if found.is_some() {
+            return found;
+        }
+        eprintln!("Input device '{}' not found, using the default device", name);
+    }
+    default()
+}
+
+// Looks for a config at `rate` with the same channel count and sample format as the default.
+fn config_with_sample_rate(
+    device: &cpal::Device,
+    default_config: &cpal::SupportedStreamConfig,
+    rate: u32,
+) -> Option<cpal::SupportedStreamConfig> {
+    device
+        .supported_input_configs()
+        .ok()?
+        .find(|range| {
+            range.channels() == default_config.channels()
+                && range.sample_format() == default_config.sample_format()
+                && range.min_sample_rate().0 <= rate
+                && rate <= range.max_sample_rate().0
+        })
+        .map(|range| range.with_sample_rate(cpal::SampleRate(rate)))
+}
+
+fn microphone_device(
+    host: &cpal::Host,
+    settings: &crate::settings::Settings,
+) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    let device = select_input_device(host, settings.input_device_name.as_deref())
+        .ok_or_else(|| "No default input device found".to_string())?;
+
+    let default_config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get default input config: {}", e))?;
+    let config = match settings.sample_rate {
+        Some(rate) => config_with_sample_rate(&device, &default_config, rate).unwrap_or_else(|| {
+            eprintln!(
+                "Input device doesn't support {} Hz, using its default of {} Hz",
+                rate,
+                default_config.sample_rate().0
+            );
+            default_config
+        }),
+        None => default_config,
+    };
+    Ok((device, config))
+}
+
+// WASAPI records an output device in loopback mode when an input stream is built on it.
+#[cfg(target_os = "windows")]
+fn loopback_device(host: &cpal::Host) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| "No default output device to record from".to_string())?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| format!("Failed to get output device config: {}", e))?;
+    Ok((device, config))
+}
+
+// PulseAudio and PipeWire expose each output as a "Monitor of ..." capture source.
+#[cfg(target_os = "linux")]
+fn loopback_device(host: &cpal::Host) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    let device = host
+        .input_devices()
+        .map_err(|e| e.to_string())?
+        .find(|device| {
+            device
+                .name()
+                .map(|name| name.to_lowercase().contains("monitor"))
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| "No PulseAudio/PipeWire monitor source found".to_string())?;
+    let config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get monitor source config: {}", e))?;
+    Ok((device, config))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn loopback_device(_host: &cpal::Host) -> Result<(cpal::Device, cpal::SupportedStreamConfig), String> {
+    Err("System audio recording isn't supported on this platform".to_string())
+}
+
+fn open_stream(app_handle: AppHandle) -> Result<(cpal::Stream, CaptureInfo), String> {
+    let host = cpal::default_host();
+    let settings = crate::settings::get_settings().unwrap_or_default();
+
+    let (device, config) = match settings.input_source {
+        InputSource::Microphone => microphone_device(&host, &settings)?,
+        InputSource::SystemLoopback => match loopback_device(&host) {
+            Ok(found) => found,
             Err(e) => {
-                eprintln!("Failed to build audio stream: {}", e);
-                return;
+                eprintln!("{}; recording from the microphone instead", e);
+                microphone_device(&host, &settings)?
             }
-        };
+        },
+    };
 
-        if let Err(e) = stream.play() {
-            eprintln!("Failed to play audio stream: {}", e);
-            return;
+    let info = CaptureInfo {
+        device: device.name().unwrap_or_default(),
+        sample_rate: config.sample_rate().0,
+        channels: config.channels(),
+    };
+    let processor_arc = get_audio_processor();
+    {
+        let mut processor = processor_arc.lock().unwrap();
+        match &mut *processor {
+            // Reopening after a disconnect keeps writing to the recording in progress.
+            Some(existing) if existing.is_recording() => existing.set_input_rate(info.sample_rate),
+            _ => *processor = Some(AudioProcessor::new(info.sample_rate)),
         }
+    }
+    DEVICE_LOST.store(false, Ordering::SeqCst);
+    set_input_gain(settings.input_gain_db);
 
-        loop {
-            thread::sleep(Duration::from_millis(100));
-        }
-    });
+    let app_handle_stream = app_handle.clone();
+    let mut visualizer = Visualizer::from_settings(&settings);
+    let channels = (config.channels() as usize).max(1);
+    let capture_channel = settings.capture_channel;
+    let stream = device.build_input_stream(
+        &config.into(),
+        move |data: &[f32], _: &cpal::InputCallbackInfo| {
+            // cpal delivers interleaved frames; everything downstream expects mono.
+            let mut samples: Vec<f32> = if channels == 1 {
+                data.to_vec()
+            } else {
+                data.chunks(channels).map(|frame| capture_channel.select(frame)).collect()
+            };
+            let gain = f32::from_bits(INPUT_GAIN.load(Ordering::Relaxed));
+            if gain != 1.0 {
+                for sample in &mut samples {
+                    *sample = (*sample * gain).clamp(-1.0, 1.0);
+                }
+            }
+            
+            let rms = Visualizer::rms(&samples);
+            LAST_INPUT_LEVEL.store(rms.to_bits(), Ordering::Relaxed);
+            CHUNKS_RECEIVED.fetch_add(1, Ordering::Relaxed);
+            
+            if MIC_TEST.load(Ordering::Relaxed) {
+                let _ = app_handle_stream.emit_to("dashboard", "mic-level", rms.min(1.0));
+            }
+            send_visualization(&app_handle_stream, &mut visualizer, &samples);
+            if let Some(processor) = &mut *processor_arc.lock().unwrap() {
+                record_chunk(&app_handle_stream, processor, &samples, rms);
+            }
+        },
+        |err| {
+            eprintln!("Audio stream error: {}", err);
+            if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                DEVICE_LOST.store(true, Ordering::SeqCst);
+            }
+        },
+        None
+    ).map_err(|e| format!("Failed to build audio stream: {}", e))?;
+
+    stream.play().map_err(|e| format!("Failed to play audio stream: {}", e))?;
+    Ok((stream, info))
 }
 
-pub fn start_recording() -> Result<(), Box<dyn std::error::Error>> {
+pub fn start_recording(bits_per_sample: u16, preroll_ms: u64, max_recording_secs: u64, volume_threshold: f32) -> Result<(), Box<dyn std::error::Error>> {
     if let Some(processor) = &mut *get_audio_processor().lock().unwrap() {
-        processor.start_recording()?;
+        processor.start_recording(bits_per_sample, preroll_ms, max_recording_secs, volume_threshold)?;
+        set_mic_test(false);
     }
     Ok(())
 }
@@ -192,4 +758,169 @@ pub fn is_recording() -> bool {
     } else {
         false
     }
-} 
\ No newline at end of file
+}
+
+pub fn last_recording_stats() -> Option<AudioStats> {
+    if let Some(processor) = &*get_audio_processor().lock().unwrap() {
+        processor.last_stats()
+    } else {
+        None
+    }
+}
+
+// True once the current recording has hit its length limit and is no longer growing.
+pub fn max_duration_reached() -> bool {
+    match &*get_audio_processor().lock().unwrap() {
+        Some(processor) => processor.is_recording() && processor.limit_reached(),
+        None => false,
+    }
+}
+
+// None when nothing is being recorded.
+pub fn voice_activity() -> Option<(u64, u64)> {
+    match &*get_audio_processor().lock().unwrap() {
+        Some(processor) if processor.is_recording() => Some(processor.voice_activity()),
+        _ => None,
+    }
+}
+
+// Measured in recorded audio, so it matches the recording-progress events. 0 when idle.
+pub fn recording_elapsed_ms() -> u64 {
+    match &*get_audio_processor().lock().unwrap() {
+        Some(processor) if processor.is_recording() => processor.elapsed_ms(),
+        _ => 0,
+    }
+}
+
+pub fn set_mic_test(enabled: bool) {
+    MIC_TEST.store(enabled, Ordering::Relaxed);
+}
+
+pub fn set_input_gain(gain_db: f32) {
+    INPUT_GAIN.store(10f32.powf(gain_db / 20.0).to_bits(), Ordering::Relaxed);
+}
+
+pub fn default_input_device_name() -> Option<String> {
+    cpal::default_host().default_input_device().and_then(|device| device.name().ok())
+}
+
+// Watches the running capture stream for `duration` and returns the loudest RMS level seen.
+pub fn probe_input(duration: Duration) -> Result<f32, String> {
+    let start_chunks = CHUNKS_RECEIVED.load(Ordering::Relaxed);
+    let deadline = Instant::now() + duration;
+    let mut max_level = 0.0f32;
+    while Instant::now() < deadline {
+        max_level = max_level.max(f32::from_bits(LAST_INPUT_LEVEL.load(Ordering::Relaxed)));
+        thread::sleep(Duration::from_millis(20));
+    }
+    if CHUNKS_RECEIVED.load(Ordering::Relaxed) == start_chunks {
+        return Err("No audio received from the input stream".to_string());
+    }
+    Ok(max_level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, sample_rate: f64, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (0.5 * (2.0 * PI * frequency * i as f64 / sample_rate).sin()) as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn resampler_output_length_follows_rate_ratio() {
+        for input_rate in [44100u32, 48000] {
+            let mut resampler = Resampler::new(input_rate as f64 / TARGET_SAMPLE_RATE as f64);
+            let input = sine(440.0, input_rate as f64, input_rate as usize);
+            let output: Vec<f32> = input.chunks(480).flat_map(|chunk| resampler.process(chunk)).collect();
+            // The last half kernel of input is held back until more audio arrives.
+            let held_back = (resampler.half_width / resampler.ratio).ceil() as usize + 1;
+            let expected = TARGET_SAMPLE_RATE as usize;
+            assert!(
+                output.len() <= expected && output.len() + held_back >= expected,
+                "{} Hz gave {} samples",
+                input_rate,
+                output.len()
+            );
+        }
+    }
+
+    #[test]
+    fn resampler_attenuates_content_above_8khz() {
+        let mut resampler = Resampler::new(3.0);
+        let input = sine(12000.0, 48000.0, 48000);
+        let output = resampler.process(&input);
+        // At 16 kHz a 12 kHz tone would alias to 4 kHz, right in the speech band.
+        assert!(rms(&output) < rms(&input) * 0.01, "rms {}", rms(&output));
+    }
+
+    #[test]
+    fn resampler_keeps_the_speech_band() {
+        let mut resampler = Resampler::new(3.0);
+        let input = sine(1000.0, 48000.0, 48000);
+        let output = resampler.process(&input);
+        let ratio = rms(&output) / rms(&input);
+        assert!((ratio - 1.0).abs() < 0.02, "ratio {}", ratio);
+    }
+
+    #[test]
+    fn recording_gets_the_raw_chunk_not_the_visualization_gain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.wav");
+        let mut processor = AudioProcessor::new(TARGET_SAMPLE_RATE);
+        processor.start_recording_to(&path, 16, 0, 0, DEFAULT_VOLUME_THRESHOLD).unwrap();
+        let mut visualizer = Visualizer { bar_count: 8, gain: 10.0, auto_gain: false, recent_peak: AUTO_GAIN_FLOOR };
+
+        // The same order as the capture callback: visualization first, then the recording.
+        let chunk: Vec<f32> = sine(440.0, TARGET_SAMPLE_RATE as f64, 1600).iter().map(|s| s * 0.1).collect();
+        let (_, volume) = visualizer.levels(&chunk);
+        assert!(volume > Visualizer::rms(&chunk) * 5.0);
+        let recorded = processor.process_audio(&chunk);
+        processor.write_samples(&recorded).unwrap();
+        processor.stop_recording().unwrap();
+
+        let written: Vec<i32> = hound::WavReader::open(&path).unwrap().samples::<i32>().map(Result::unwrap).collect();
+        let expected: Vec<i32> = chunk.iter().map(|&s| (s * sample_scale(16)) as i32).collect();
+        assert_eq!(written, expected);
+    }
+
+    fn devices() -> Vec<(Option<String>, &'static str)> {
+        vec![(Some("Built-in Microphone".to_string()), "built-in"), (Some("USB Mic".to_string()), "usb")]
+    }
+
+    #[test]
+    fn named_device_is_chosen_when_connected() {
+        assert_eq!(choose_device(Some("USB Mic"), devices(), || Some("default")), Some("usb"));
+    }
+
+    #[test]
+    fn missing_device_falls_back_to_the_default() {
+        assert_eq!(choose_device(Some("Headset"), devices(), || Some("default")), Some("default"));
+        assert_eq!(choose_device(None, devices(), || Some("default")), Some("default"));
+    }
+
+    #[test]
+    fn capture_gives_up_when_no_device_can_be_selected() {
+        // The unplugged USB mic is gone and there is no default device either.
+        let reopened = recover_capture(|| {
+            choose_device(Some("USB Mic"), Vec::<(Option<String>, &str)>::new(), || None)
+                .ok_or_else(|| "No default input device found".to_string())
+        });
+        assert_eq!(reopened, None);
+    }
+
+    #[test]
+    fn capture_continues_on_the_reselected_device() {
+        let reopened = recover_capture(|| {
+            choose_device(Some("USB Mic"), devices()[..1].to_vec(), || Some("default"))
+                .ok_or_else(|| "No default input device found".to_string())
+        });
+        assert_eq!(reopened, Some("default"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{command, AppHandle, Emitter};
+use crate::hotkey::HotkeyAction;
+use crate::{audio, settings, start_session, submit_recording, take_pending_job, ProcessingJob};
+
+// Segments with less speech than this are treated as noise and dropped.
+const MIN_SPEECH_MS: u64 = 300;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+// Still set while the session winds down after a stop request.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+// Keeps recording until stopped, handing each utterance to the transcription queue as
+// soon as the speaker pauses. The queue delivers segments one after another in the
+// order they were spoken, so injections never overlap.
+pub fn start(app: AppHandle, action: HotkeyAction) -> Result<(), String> {
+    if ACTIVE.swap(true, Ordering::SeqCst) {
+        return Err("Continuous dictation is already running".to_string());
+    }
+    if let Err(e) = start_session(ProcessingJob::new(action.clone())) {
+        ACTIVE.store(false, Ordering::SeqCst);
+        return Err(e);
+    }
+    RUNNING.store(true, Ordering::SeqCst);
+    let _ = app.emit("continuous-dictation", "started");
+
+    // A pause at least this long ends the current utterance.
+    let settings = settings::get_settings().unwrap_or_default();
+    let pause_ms = settings.silence_autostop_enabled.then_some(settings.silence_timeout_ms);
+
+    thread::spawn(move || {
+        while is_running() {
+            thread::sleep(POLL_INTERVAL);
+            let (speech_ms, silence_ms) = match audio::voice_activity() {
+                Some(activity) => activity,
+                None => break,
+            };
+            // Long monologues are handed off at the length limit instead of being cut short.
+            let paused = pause_ms.is_some_and(|pause_ms| speech_ms >= MIN_SPEECH_MS && silence_ms >= pause_ms);
+            if paused || audio::max_duration_reached() {
+                if let Err(e) = submit_recording(&app, None, take_pending_job()) {
+                    eprintln!("Failed to queue dictated segment: {}", e);
+                }
+                if let Err(e) = start_session(ProcessingJob::new(action.clone())) {
+                    eprintln!("Failed to resume continuous dictation: {}", e);
+                    break;
+                }
+            }
+        }
+
+        // Whatever was said since the last pause still gets transcribed.
+        match audio::voice_activity() {
+            Some((speech_ms, _)) if speech_ms >= MIN_SPEECH_MS => {
+                if let Err(e) = submit_recording(&app, None, take_pending_job()) {
+                    eprintln!("Failed to queue dictated segment: {}", e);
+                }
+            }
+            _ => {
+                if let Err(e) = audio::stop_recording() {
+                    eprintln!("Failed to stop recording: {}", e);
+                }
+                take_pending_job();
+            }
+        }
+        RUNNING.store(false, Ordering::SeqCst);
+        ACTIVE.store(false, Ordering::SeqCst);
+        let _ = app.emit("continuous-dictation", "stopped");
+    });
+    Ok(())
+}
+
+pub fn stop() {
+    RUNNING.store(false, Ordering::SeqCst);
+}
+
+#[command]
+pub fn start_continuous_dictation(app: AppHandle) -> Result<(), String> {
+    start(app, HotkeyAction::default())
+}
+
+#[command]
+pub fn stop_continuous_dictation() {
+    stop();
+}
@@ -0,0 +1,178 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use serde::Serialize;
+use tauri::command;
+use crate::hotkey::HotkeyAction;
+use crate::{audio, settings, textinjection, transcription, ProcessingJob};
+
+// Input levels below this over the probe window most likely mean a muted mic.
+const MIN_INPUT_LEVEL: f32 = 0.0005;
+
+#[derive(Debug, Serialize)]
+pub struct SelfTestStep {
+    pub name: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub steps: Vec<SelfTestStep>,
+}
+
+fn step(name: &str, result: Result<String, String>) -> SelfTestStep {
+    match result {
+        Ok(message) => SelfTestStep { name: name.to_string(), passed: true, message },
+        Err(message) => SelfTestStep { name: name.to_string(), passed: false, message },
+    }
+}
+
+fn self_test() -> SelfTestReport {
+    let mut steps = Vec::new();
+
+    steps.push(step(
+        "input_device",
+        audio::default_input_device_name()
+            .map(|name| format!("Using {}", name))
+            .ok_or_else(|| "No input device found".to_string()),
+    ));
+
+    steps.push(step(
+        "audio_capture",
+        audio::probe_input(Duration::from_millis(500)).and_then(|level| {
+            if level >= MIN_INPUT_LEVEL {
+                Ok(format!("Peak input level {:.4}", level))
+            } else {
+                Err("Input is silent; check that the microphone is not muted".to_string())
+            }
+        }),
+    ));
+
+    let settings = settings::get_settings().unwrap_or_default();
+    let api_key = settings.groq_api_key.clone().filter(|k| !k.is_empty());
+    steps.push(step(
+        "groq_api",
+        match api_key {
+            Some(key) => transcription::check_api_key(&key, &settings.network).map(|_| "API key accepted".to_string()),
+            None => Err("No Groq API key set".to_string()),
+        },
+    ));
+
+    steps.push(step(
+        "text_injection",
+        if textinjection::is_text_injector_initialized() {
+            Ok("Text injector ready".to_string())
+        } else {
+            Err("Text injector not initialized".to_string())
+        },
+    ));
+
+    SelfTestReport {
+        passed: steps.iter().all(|s| s.passed),
+        steps,
+    }
+}
+
+// The capture probe and the API check can take seconds, so they run off the main thread.
+#[command]
+pub async fn run_self_test() -> Result<SelfTestReport, String> {
+    tauri::async_runtime::spawn_blocking(self_test)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Sample clip used by the benchmark: two seconds of a voiced-sounding tone.
+const BENCHMARK_CLIP_MS: u32 = 2000;
+const MAX_BENCHMARK_ITERATIONS: usize = 50;
+
+static BENCHMARK_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub iterations: usize,
+    pub successes: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: f64,
+    pub cancelled: bool,
+}
+
+// Generated rather than bundled so every run sends byte-identical audio.
+fn write_benchmark_clip(path: &Path) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(|e| e.to_string())?;
+    let total = spec.sample_rate * BENCHMARK_CLIP_MS / 1000;
+    for i in 0..total {
+        let t = i as f32 / spec.sample_rate as f32;
+        let envelope = (std::f32::consts::PI * t * 2.0).sin().abs();
+        let sample: f32 = (1..=4)
+            .map(|h| (2.0 * std::f32::consts::PI * 150.0 * h as f32 * t).sin() / h as f32)
+            .sum();
+        writer
+            .write_sample((sample * envelope * 0.3 * i16::MAX as f32) as i16)
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+fn run_benchmark(iterations: usize) -> Result<BenchmarkReport, String> {
+    let job = ProcessingJob::new(HotkeyAction::default());
+    let api_key = job.settings.api_key()?;
+    let options = job.transcription_options();
+    let clip = std::env::temp_dir().join("vwisper_benchmark.wav");
+    write_benchmark_clip(&clip)?;
+
+    BENCHMARK_CANCELLED.store(false, Ordering::SeqCst);
+    let mut latencies = Vec::new();
+    let mut successes = 0;
+    let mut cancelled = false;
+    // Results are deliberately kept out of history so they don't skew usage stats.
+    for _ in 0..iterations.clamp(1, MAX_BENCHMARK_ITERATIONS) {
+        if BENCHMARK_CANCELLED.load(Ordering::SeqCst) {
+            cancelled = true;
+            break;
+        }
+        let result = transcription::transcribe_audio(&clip.to_string_lossy(), &api_key, &options);
+        if result.status == "success" {
+            successes += 1;
+        }
+        latencies.push(result.round_trip_ms);
+    }
+    let _ = std::fs::remove_file(&clip);
+
+    latencies.sort_unstable();
+    let mean_ms = if latencies.is_empty() {
+        0.0
+    } else {
+        latencies.iter().sum::<u64>() as f64 / latencies.len() as f64
+    };
+    Ok(BenchmarkReport {
+        iterations: latencies.len(),
+        successes,
+        min_ms: latencies.first().copied().unwrap_or(0),
+        median_ms: latencies.get(latencies.len() / 2).copied().unwrap_or(0),
+        max_ms: latencies.last().copied().unwrap_or(0),
+        mean_ms,
+        cancelled,
+    })
+}
+
+#[command]
+pub async fn benchmark_transcription(iterations: usize) -> Result<BenchmarkReport, String> {
+    tauri::async_runtime::spawn_blocking(move || run_benchmark(iterations))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub fn cancel_benchmark() {
+    BENCHMARK_CANCELLED.store(true, Ordering::SeqCst);
+}
@@ -0,0 +1,77 @@
+use std::sync::Mutex;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+// Encrypts history text at rest. The key is random and lives only in the OS keychain
+// (Keychain, Credential Manager or the Secret Service), so if that entry is deleted,
+// text encrypted with it can't be recovered.
+
+const KEYRING_SERVICE: &str = "vwisper";
+const KEYRING_USER: &str = "history-key";
+// Marks encrypted text, followed by base64 of the nonce and ciphertext.
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+static KEY: Mutex<Option<Key>> = Mutex::new(None);
+
+fn key() -> Result<Key, String> {
+    let mut cached = KEY.lock().unwrap();
+    if let Some(key) = *cached {
+        return Ok(key);
+    }
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| e.to_string())?;
+    let key = match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = STANDARD.decode(encoded).map_err(|e| format!("Corrupt history key: {}", e))?;
+            if bytes.len() != 32 {
+                return Err("Corrupt history key: wrong length".to_string());
+            }
+            *Key::from_slice(&bytes)
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+            entry
+                .set_password(&STANDARD.encode(key))
+                .map_err(|e| format!("Failed to store history key: {}", e))?;
+            key
+        }
+        Err(e) => return Err(format!("Failed to read history key: {}", e)),
+    };
+    *cached = Some(key);
+    Ok(key)
+}
+
+pub fn is_encrypted(text: &str) -> bool {
+    text.starts_with(PREFIX)
+}
+
+pub fn encrypt(text: &str) -> Result<String, String> {
+    if is_encrypted(text) {
+        return Ok(text.to_string());
+    }
+    let cipher = ChaCha20Poly1305::new(&key()?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, text.as_bytes()).map_err(|e| e.to_string())?;
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", PREFIX, STANDARD.encode(payload)))
+}
+
+// Plain text is passed through unchanged.
+pub fn decrypt(text: &str) -> Result<String, String> {
+    let Some(encoded) = text.strip_prefix(PREFIX) else {
+        return Ok(text.to_string());
+    };
+    let payload = STANDARD.decode(encoded).map_err(|e| e.to_string())?;
+    if payload.len() < NONCE_LEN {
+        return Err("Encrypted text is truncated".to_string());
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(&key()?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt text; the history key may have changed".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
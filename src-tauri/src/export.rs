@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+use crate::history::TranscriptionEntry;
+use crate::usage_stats::{entry_duration_ms, word_count};
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Txt,
+}
+
+// Quotes every field the way spreadsheet apps expect, doubling embedded quotes so commas
+// and newlines inside the text survive.
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn to_csv(entries: &[TranscriptionEntry]) -> String {
+    let mut out = String::from("timestamp,duration_ms,word_count,success,text\r\n");
+    for entry in entries {
+        let duration = entry_duration_ms(entry).map(|ms| ms.to_string()).unwrap_or_default();
+        out.push_str(&format!(
+            "{},{},{},{},{}\r\n",
+            entry.timestamp.to_rfc3339(),
+            duration,
+            word_count(&entry.text),
+            entry.status == "success",
+            csv_field(&entry.text)
+        ));
+    }
+    out
+}
+
+// One paragraph per entry, each headed by its local time.
+fn to_txt(entries: &[TranscriptionEntry]) -> String {
+    entries
+        .iter()
+        .filter(|entry| !entry.text.trim().is_empty())
+        .map(|entry| {
+            format!(
+                "[{}]\n{}\n",
+                entry.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+                entry.text.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Returns how many entries were written. Text exports leave out entries without text.
+pub fn export(entries: &[TranscriptionEntry], format: ExportFormat, path: &Path) -> Result<usize, String> {
+    let (contents, count) = match format {
+        ExportFormat::Csv => (to_csv(entries), entries.len()),
+        ExportFormat::Json => (serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?, entries.len()),
+        ExportFormat::Txt => (
+            to_txt(entries),
+            entries.iter().filter(|entry| !entry.text.trim().is_empty()).count(),
+        ),
+    };
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(count)
+}
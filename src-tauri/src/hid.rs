@@ -0,0 +1,181 @@
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle};
+use crate::hotkey::HotkeyAction;
+use crate::settings;
+
+// Foot pedals and similar devices that show up as ordinary keyboards can already be
+// bound on every platform with a `Raw(<code>)` hotkey. This covers devices read
+// directly through evdev, which is only available on Linux.
+
+// How long `detect_hid_key` waits for the user to press something.
+#[cfg(target_os = "linux")]
+const DETECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HidTrigger {
+    // e.g. /dev/input/event5
+    pub device: String,
+    // evdev key code, e.g. 30 for KEY_A or 256 for BTN_0
+    pub code: u16,
+    // Overrides for sessions started by the trigger, e.g. continuous dictation.
+    #[serde(default)]
+    pub action: HotkeyAction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InputDevice {
+    pub path: String,
+    pub name: String,
+}
+
+#[cfg(target_os = "linux")]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
+
+#[cfg(target_os = "linux")]
+mod evdev {
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Read};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::time::{Duration, Instant};
+    use super::{InputDevice, KeyState};
+
+    const EV_KEY: u16 = 0x01;
+    // The timestamp's size depends on the target, so the layout comes from libc.
+    const EVENT_SIZE: usize = std::mem::size_of::<libc::input_event>();
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    // Auto-repeat and non-key events give None.
+    fn parse_event(buffer: &[u8; EVENT_SIZE]) -> Option<(u16, KeyState)> {
+        // input_event is plain integers, so any EVENT_SIZE bytes make a valid one.
+        let event: libc::input_event = unsafe { std::ptr::read_unaligned(buffer.as_ptr().cast()) };
+        if event.type_ != EV_KEY {
+            return None;
+        }
+        match event.value {
+            1 => Some((event.code, KeyState::Pressed)),
+            0 => Some((event.code, KeyState::Released)),
+            _ => None,
+        }
+    }
+
+    pub fn list_devices() -> Vec<InputDevice> {
+        let mut devices: Vec<InputDevice> = fs::read_dir("/sys/class/input")
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let file_name = entry.file_name().to_string_lossy().to_string();
+                        if !file_name.starts_with("event") {
+                            return None;
+                        }
+                        let name = fs::read_to_string(entry.path().join("device/name"))
+                            .map(|name| name.trim().to_string())
+                            .unwrap_or_default();
+                        Some(InputDevice { path: format!("/dev/input/{}", file_name), name })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        devices.sort_by(|a, b| a.path.cmp(&b.path));
+        devices
+    }
+
+    pub struct EventReader {
+        file: File,
+    }
+
+    impl EventReader {
+        // Non-blocking, so readers can give up instead of holding the device open forever.
+        pub fn open_nonblocking(path: &str) -> Result<Self, String> {
+            // Reading /dev/input usually requires membership of the `input` group.
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+            Ok(Self { file })
+        }
+
+        // Waits for the next key press or release, skipping auto-repeat; None once `deadline` passes.
+        pub fn next_key_until(&mut self, deadline: Instant) -> Result<Option<(u16, KeyState)>, String> {
+            let mut buffer = [0u8; EVENT_SIZE];
+            while Instant::now() < deadline {
+                // evdev hands out whole events, so a read is either one event or nothing.
+                match self.file.read(&mut buffer) {
+                    Ok(EVENT_SIZE) => {
+                        if let Some(key) = parse_event(&buffer) {
+                            return Ok(Some(key));
+                        }
+                    }
+                    Ok(_) => return Err("Short read from input device".to_string()),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+                    Err(e) => return Err(e.to_string()),
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use evdev::EventReader;
+
+#[command]
+pub fn list_hid_devices() -> Result<Vec<InputDevice>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(evdev::list_devices())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Direct device access is only supported on Linux; bind the pedal as a Raw(<code>) hotkey instead".to_string())
+    }
+}
+
+// Waits for the next key press on `device` and returns its code, so the settings UI can
+// ask the user to press the pedal instead of looking the code up.
+#[command]
+pub async fn detect_hid_key(device: String) -> Result<u16, String> {
+    #[cfg(target_os = "linux")]
+    {
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut reader = EventReader::open_nonblocking(&device)?;
+            let deadline = std::time::Instant::now() + DETECT_TIMEOUT;
+            loop {
+                match reader.next_key_until(deadline)? {
+                    Some((code, KeyState::Pressed)) => break Ok(code),
+                    Some(_) => continue,
+                    None => break Err("No key press detected".to_string()),
+                }
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = device;
+        Err("Direct device access is only supported on Linux".to_string())
+    }
+}
+
+// Restarts the HID monitor with the new trigger, so it applies without relaunching.
+#[command]
+pub fn set_hid_trigger(app: AppHandle, device: Option<String>, code: Option<u16>, action: Option<HotkeyAction>) -> Result<(), String> {
+    let trigger = match (device, code) {
+        (Some(device), Some(code)) => Some(HidTrigger { device, code, action: action.unwrap_or_default() }),
+        (None, _) => None,
+        (Some(_), None) => return Err("A key code is required".to_string()),
+    };
+    settings::update_settings(&app, |settings| settings.hid_trigger = trigger.clone())?;
+    #[cfg(target_os = "linux")]
+    crate::platform::linux::restart_hid_monitor(app, trigger);
+    Ok(())
+}
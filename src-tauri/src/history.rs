@@ -1,9 +1,19 @@
 use std::sync::{Arc, Mutex};
-use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Serialize, Deserialize};
 use std::fs;
-use std::path::PathBuf;
-use dirs::config_dir;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::encryption;
+use crate::storage;
+
+const HISTORY_SCHEMA_VERSION: u32 = 1;
+// New entries are batched and written at most once per this interval.
+const FLUSH_DELAY: Duration = Duration::from_secs(2);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TranscriptionEntry {
@@ -14,52 +24,440 @@ pub struct TranscriptionEntry {
     pub hold_time_ms: Option<u64>,
     pub status: String,
     pub wav_path: Option<String>,
+    #[serde(default)]
+    pub audio_stats: Option<AudioStats>,
+    #[serde(default)]
+    pub segments: Option<Vec<Segment>>,
+    // Per-word timings, with each word as the segment text.
+    #[serde(default)]
+    pub words: Option<Vec<Segment>>,
+    // Why the session failed, as shown to the user.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<TranscriptionEntry>,
+    pub total: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    Text,
+    // Transcribed without error, but nothing usable was said.
+    Empty,
+    Failed,
+}
+
+impl TranscriptionEntry {
+    pub fn outcome(&self) -> SessionOutcome {
+        if self.status != "success" {
+            SessionOutcome::Failed
+        } else if self.text.trim().is_empty() {
+            SessionOutcome::Empty
+        } else {
+            SessionOutcome::Text
+        }
+    }
+}
+
+// A timestamped piece of a transcript; times are seconds from the start of the recording.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AudioStats {
+    pub peak_level: f32,
+    pub rms_level: f32,
+    pub clipped_samples: u64,
+    pub duration_ms: u64,
+    // Fraction of captured chunks that contained speech rather than silence.
+    #[serde(default)]
+    pub speech_ratio: Option<f32>,
+    // Time from record start to stop. duration_ms is derived from the samples written.
+    #[serde(default)]
+    pub wall_clock_ms: Option<u64>,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct HistoryData {
+    #[serde(default)]
+    pub schema_version: u32,
     pub entries: Vec<TranscriptionEntry>,
 }
 
 #[derive(Clone, Default)]
 pub struct History {
     pub entries: Arc<Mutex<Vec<TranscriptionEntry>>>,
+    dirty: Arc<AtomicBool>,
+    flush_scheduled: Arc<AtomicBool>,
 }
 
 fn history_path() -> PathBuf {
-    let mut path = config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("vwisper");
-    fs::create_dir_all(&path).ok();
-    path.push("history.json");
-    path
+    storage::data_file("history.json")
+}
+
+fn migrate_history(value: &mut Value, from_version: u32) {
+    if from_version < 1 {
+        // The earliest builds wrote the entry list as a bare array.
+        if value.is_array() {
+            *value = serde_json::json!({ "entries": value.take() });
+        }
+        if let Some(entries) = value.get_mut("entries").and_then(Value::as_array_mut) {
+            for entry in entries.iter_mut().filter_map(Value::as_object_mut) {
+                entry.entry("text").or_insert_with(|| Value::from(""));
+                entry.entry("status").or_insert_with(|| Value::from("success"));
+            }
+        }
+    }
+}
+
+// Shortens `text` to at most `max_chars` graphemes for display, preferring to cut at the
+// last word boundary so words, emoji and combining sequences are never split.
+pub fn preview(text: &str, max_chars: usize) -> String {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    if graphemes.len() <= max_chars {
+        return text.to_string();
+    }
+    let kept = &graphemes[..max_chars];
+    let cut = kept
+        .iter()
+        .rposition(|g| g.chars().all(char::is_whitespace))
+        .filter(|&index| index > 0)
+        .unwrap_or(max_chars);
+    format!("{}…", kept[..cut].concat().trim_end())
+}
+
+fn remove_audio_file(entry: &TranscriptionEntry) {
+    if let Some(path) = &entry.wav_path {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Set from the encrypt_history setting. Entries are always decrypted on load, so turning it
+// off writes everything back as plain text on the next save.
+static ENCRYPT_AT_REST: AtomicBool = AtomicBool::new(false);
+
+pub fn set_encryption(enabled: bool) {
+    ENCRYPT_AT_REST.store(enabled, Ordering::SeqCst);
+}
+
+// Applies `f` to the transcript and to the text of its segments and words.
+fn map_texts<F: Fn(&str) -> Result<String, String>>(entry: &mut TranscriptionEntry, f: F) -> Result<(), String> {
+    entry.text = f(&entry.text)?;
+    for segment in entry.segments.iter_mut().chain(entry.words.iter_mut()).flatten() {
+        segment.text = f(&segment.text)?;
+    }
+    Ok(())
+}
+
+fn save_entries(entries: &[TranscriptionEntry]) {
+    let mut entries = entries.to_vec();
+    if ENCRYPT_AT_REST.load(Ordering::SeqCst) {
+        // Plain text is never written while encryption is on, even if the key is unavailable.
+        if let Err(e) = entries.iter_mut().try_for_each(|entry| map_texts(entry, encryption::encrypt)) {
+            eprintln!("Failed to save history: {}", e);
+            return;
+        }
+    }
+    let data = HistoryData {
+        schema_version: HISTORY_SCHEMA_VERSION,
+        entries,
+    };
+    let result = serde_json::to_string_pretty(&data)
+        .map_err(|e| e.to_string())
+        .and_then(|json| storage::write_atomic(&history_path(), &json).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to save history: {}", e);
+    }
+}
+
+fn load_entries() -> Vec<TranscriptionEntry> {
+    let data: HistoryData = storage::load_versioned(&history_path(), HISTORY_SCHEMA_VERSION, migrate_history);
+    let mut entries = data.entries;
+    for entry in entries.iter_mut().filter(|entry| encryption::is_encrypted(&entry.text)) {
+        // Left encrypted when it can't be decrypted, so it isn't lost on the next save.
+        if let Err(e) = map_texts(entry, encryption::decrypt) {
+            eprintln!("Failed to decrypt history entry {}: {}", entry.id, e);
+        }
+    }
+    entries
 }
 
 impl History {
     pub fn new() -> Self {
-        let entries = if let Ok(data) = fs::read_to_string(history_path()) {
-            serde_json::from_str::<HistoryData>(&data).map(|d| d.entries).unwrap_or_default()
-        } else {
-            Vec::new()
-        };
         Self {
-            entries: Arc::new(Mutex::new(entries)),
+            entries: Arc::new(Mutex::new(load_entries())),
+            ..Default::default()
         }
     }
 
-    pub fn add_entry(&self, entry: TranscriptionEntry) {
+    // Replaces the in-memory entries with what's on disk, e.g. after the file was restored
+    // from a backup. Changes not yet flushed are discarded.
+    pub fn reload(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        *entries = load_entries();
+        self.dirty.store(false, Ordering::SeqCst);
+        entries.len()
+    }
+
+    // `max_entries` of 0 keeps every entry; otherwise the oldest are dropped.
+    pub fn add_entry(&self, entry: TranscriptionEntry, max_entries: usize) {
         let mut entries = self.entries.lock().unwrap();
         entries.push(entry);
-        let data = HistoryData {
-            entries: entries.clone(),
+        if max_entries > 0 && entries.len() > max_entries {
+            let excess = entries.len() - max_entries;
+            for removed in entries.drain(..excess) {
+                remove_audio_file(&removed);
+            }
+        }
+        drop(entries);
+        self.mark_dirty();
+    }
+
+    // Deletes the recordings of all but the newest `keep` entries that have one. The
+    // entries themselves stay in the history.
+    pub fn prune_audio(&self, keep: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut pruned = false;
+        for entry in entries.iter_mut().rev().filter(|entry| entry.wav_path.is_some()).skip(keep) {
+            remove_audio_file(entry);
+            entry.wav_path = None;
+            pruned = true;
+        }
+        drop(entries);
+        if pruned {
+            self.mark_dirty();
+        }
+    }
+
+    // Removes entries whose timestamp falls within `start..=end` and returns how many were removed.
+    pub fn delete_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> usize {
+        self.delete_where(|entry| entry.timestamp >= start && entry.timestamp <= end)
+    }
+
+    pub fn delete_entry(&self, id: &str) -> bool {
+        self.delete_where(|entry| entry.id == id) > 0
+    }
+
+    pub fn clear(&self) -> usize {
+        self.delete_where(|_| true)
+    }
+
+    pub fn delete_older_than(&self, days: u64) -> usize {
+        let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+        self.delete_where(|entry| entry.timestamp < cutoff)
+    }
+
+    fn delete_where<F: Fn(&TranscriptionEntry) -> bool>(&self, predicate: F) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|entry| {
+            if predicate(entry) {
+                remove_audio_file(entry);
+                false
+            } else {
+                true
+            }
+        });
+        let removed = before - entries.len();
+        drop(entries);
+        if removed > 0 {
+            self.mark_dirty();
+        }
+        removed
+    }
+
+    // Points saved recordings at their new location after the audio directory moved.
+    pub fn relocate_audio(&self, from: &Path, to: &Path) {
+        let mut entries = self.entries.lock().unwrap();
+        for entry in entries.iter_mut() {
+            let relocated = entry
+                .wav_path
+                .as_deref()
+                .and_then(|path| Path::new(path).strip_prefix(from).ok())
+                .map(|relative| to.join(relative).to_string_lossy().to_string());
+            if relocated.is_some() {
+                entry.wav_path = relocated;
+            }
+        }
+        drop(entries);
+        self.mark_dirty();
+    }
+
+    // Returns false when no entry has that id.
+    pub fn update_entry<F: FnOnce(&mut TranscriptionEntry)>(&self, id: &str, update: F) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.iter_mut().find(|entry| entry.id == id) else {
+            return false;
         };
-        let _ = fs::write(
-            history_path(),
-            serde_json::to_string_pretty(&data).unwrap_or_default(),
-        );
+        update(entry);
+        drop(entries);
+        self.mark_dirty();
+        true
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+        if !self.flush_scheduled.swap(true, Ordering::SeqCst) {
+            let history = self.clone();
+            thread::spawn(move || {
+                thread::sleep(FLUSH_DELAY);
+                history.flush_scheduled.store(false, Ordering::SeqCst);
+                history.flush();
+            });
+        }
+    }
+
+    // Writes every entry again, e.g. to encrypt or decrypt them after the setting changed.
+    pub fn rewrite(&self) {
+        self.mark_dirty();
+    }
+
+    pub fn flush(&self) {
+        if self.dirty.swap(false, Ordering::SeqCst) {
+            let entries = self.entries.lock().unwrap();
+            save_entries(&entries);
+        }
     }
 
     pub fn get_entries(&self) -> Vec<TranscriptionEntry> {
         let entries = self.entries.lock().unwrap();
         entries.clone()
     }
+
+    // Newest first.
+    pub fn get_recent_entries(&self, count: usize) -> Vec<TranscriptionEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().rev().take(count).cloned().collect()
+    }
+
+    // Case-insensitive substring match on the text, newest first.
+    pub fn search(&self, query: &str) -> Vec<TranscriptionEntry> {
+        let query = query.to_lowercase();
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.text.to_lowercase().contains(&query))
+            .cloned()
+            .collect()
+    }
+
+    // Newest first. `from` and `to` are inclusive UTC dates; `total` counts every entry in
+    // the range, not just the returned page.
+    pub fn page(&self, offset: usize, limit: usize, from: Option<NaiveDate>, to: Option<NaiveDate>) -> HistoryPage {
+        let entries = self.entries.lock().unwrap();
+        let in_range = |entry: &&TranscriptionEntry| {
+            let date = entry.timestamp.date_naive();
+            !from.is_some_and(|from| date < from) && !to.is_some_and(|to| date > to)
+        };
+        HistoryPage {
+            total: entries.iter().filter(in_range).count(),
+            entries: entries.iter().rev().filter(in_range).skip(offset).take(limit).cloned().collect(),
+        }
+    }
+
+    // Entries whose UTC timestamp falls on `date`, newest first.
+    pub fn entries_on(&self, date: NaiveDate) -> Vec<TranscriptionEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.timestamp.date_naive() == date)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> HistoryData {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.json");
+        fs::write(&path, contents).unwrap();
+        storage::load_versioned(&path, HISTORY_SCHEMA_VERSION, migrate_history)
+    }
+
+    #[test]
+    fn bare_array_history_is_migrated() {
+        let data = load(r#"[{ "id": "a", "timestamp": "2024-05-01T10:00:00Z", "round_trip_ms": 420, "hold_time_ms": null, "wav_path": null }]"#);
+        assert_eq!(data.schema_version, HISTORY_SCHEMA_VERSION);
+        assert_eq!(data.entries.len(), 1);
+        assert_eq!(data.entries[0].id, "a");
+        assert_eq!(data.entries[0].text, "");
+        assert_eq!(data.entries[0].status, "success");
+    }
+
+    #[test]
+    fn v1_history_loads_into_the_current_entry() {
+        let data = load(
+            r#"{
+                "schema_version": 1,
+                "entries": [{
+                    "id": "b",
+                    "timestamp": "2024-05-01T10:00:00Z",
+                    "text": "hello",
+                    "round_trip_ms": 300,
+                    "hold_time_ms": 1200,
+                    "status": "success",
+                    "wav_path": null
+                }]
+            }"#,
+        );
+        let entry = &data.entries[0];
+        assert_eq!(entry.text, "hello");
+        assert_eq!(entry.hold_time_ms, Some(1200));
+        assert!(entry.audio_stats.is_none());
+        assert!(entry.segments.is_none());
+        assert!(entry.words.is_none());
+        assert!(entry.error.is_none());
+    }
+
+    fn aged_entry(id: &str, days_old: i64, wav_path: &Path) -> TranscriptionEntry {
+        fs::write(wav_path, b"").unwrap();
+        TranscriptionEntry {
+            id: id.to_string(),
+            timestamp: Utc::now() - chrono::Duration::days(days_old),
+            text: id.to_string(),
+            round_trip_ms: None,
+            hold_time_ms: None,
+            status: "success".to_string(),
+            wav_path: Some(wav_path.to_string_lossy().into_owned()),
+            audio_stats: None,
+            segments: None,
+            words: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn entries_past_the_cutoff_are_pruned_with_their_audio() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.wav");
+        let recent = dir.path().join("recent.wav");
+        let history = History {
+            entries: Arc::new(Mutex::new(vec![aged_entry("old", 40, &old), aged_entry("recent", 5, &recent)])),
+            // Already counted as scheduled, so pruning doesn't write to the real history file.
+            flush_scheduled: Arc::new(AtomicBool::new(true)),
+            ..Default::default()
+        };
+
+        assert_eq!(history.delete_older_than(30), 1);
+        let remaining = history.get_entries();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, "recent");
+        assert!(!old.exists());
+        assert!(recent.exists());
+
+        assert_eq!(history.delete_older_than(30), 0);
+    }
 }
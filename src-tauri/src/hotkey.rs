@@ -0,0 +1,275 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use rdev::{EventType, Key};
+use serde::{Deserialize, Serialize};
+use crate::settings::{InjectSubmit, RecordingMode, Settings};
+
+// Presses closer together than this are treated as key bounce.
+const DEBOUNCE: Duration = Duration::from_millis(25);
+
+// Set by the platform monitors while they are receiving key events.
+static MONITORING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_monitoring(active: bool) {
+    MONITORING.store(active, Ordering::SeqCst);
+}
+
+pub fn is_monitoring() -> bool {
+    MONITORING.load(Ordering::SeqCst)
+}
+
+// Bumped when hotkey settings change; monitors rebuild from settings on their next event.
+static SETTINGS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn reload_monitors() {
+    SETTINGS_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+// Set while a push-to-talk or toggle binding has a recording running.
+static KEY_SESSION: AtomicBool = AtomicBool::new(false);
+
+// Ends the running key session on behalf of its key, e.g. at the length limit, so the
+// monitors ignore that binding until it is next released. False if none was running.
+pub fn end_key_session() -> bool {
+    KEY_SESSION.swap(false, Ordering::SeqCst)
+}
+
+// Per-binding overrides applied to the session started by that hotkey.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyAction {
+    pub language: Option<String>,
+    pub model: Option<String>,
+    pub inject_submit: Option<InjectSubmit>,
+    pub translate: Option<bool>,
+    // Press once to start hands-free dictation and again to stop it.
+    pub continuous: bool,
+    // Turn spoken commands such as "open paren" into symbols, for dictating code.
+    pub code_mode: bool,
+    pub prompt: Option<String>,
+}
+
+impl HotkeyAction {
+    // Fills in whatever this action leaves unset from `defaults`, e.g. the active preset.
+    pub fn with_defaults(self, defaults: &HotkeyAction) -> HotkeyAction {
+        HotkeyAction {
+            language: self.language.or_else(|| defaults.language.clone()),
+            model: self.model.or_else(|| defaults.model.clone()),
+            inject_submit: self.inject_submit.or(defaults.inject_submit),
+            translate: self.translate.or(defaults.translate),
+            continuous: self.continuous,
+            code_mode: self.code_mode || defaults.code_mode,
+            prompt: self.prompt.or_else(|| defaults.prompt.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HotkeyBinding {
+    pub key: String,
+    #[serde(default)]
+    pub action: HotkeyAction,
+}
+
+pub fn default_hotkey() -> &'static str {
+    #[cfg(target_os = "macos")]
+    {
+        "Function"
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        "Control"
+    }
+}
+
+const NAMED_KEYS: &[Key] = &[
+    Key::Alt, Key::AltGr, Key::ControlLeft, Key::ControlRight, Key::ShiftLeft, Key::ShiftRight,
+    Key::MetaLeft, Key::MetaRight, Key::Function, Key::CapsLock, Key::Escape, Key::Space, Key::Tab,
+    Key::Insert, Key::Home, Key::End, Key::PageUp, Key::PageDown, Key::Pause, Key::ScrollLock,
+    Key::PrintScreen, Key::NumLock, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7,
+    Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+];
+
+// Parses a key name into the physical keys it matches. Generic modifier names match
+// both sides, rdev key names match exactly, and `Raw(<code>)` matches a raw key code.
+pub fn parse_key(name: &str) -> Option<Vec<Key>> {
+    let name = name.trim();
+    let keys = match name.to_lowercase().as_str() {
+        "control" | "ctrl" => vec![Key::ControlLeft, Key::ControlRight],
+        "shift" => vec![Key::ShiftLeft, Key::ShiftRight],
+        "meta" | "cmd" | "command" | "super" | "win" => vec![Key::MetaLeft, Key::MetaRight],
+        "option" => vec![Key::Alt],
+        "fn" => vec![Key::Function],
+        lower => {
+            if let Some(code) = lower.strip_prefix("raw(").and_then(|rest| rest.strip_suffix(')')) {
+                vec![Key::Unknown(code.trim().parse().ok()?)]
+            } else {
+                let key = NAMED_KEYS
+                    .iter()
+                    .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))?;
+                vec![*key]
+            }
+        }
+    };
+    Some(keys)
+}
+
+// evdev codes have no rdev key, so triggers go through the monitor as unknown keys.
+pub fn trigger_key(code: u16) -> Key {
+    Key::Unknown(code as u32)
+}
+
+pub enum HotkeyEvent {
+    Pressed(HotkeyAction),
+    Released(HotkeyAction),
+    // The cancel key was pressed while a binding was held.
+    Cancelled,
+    // The cancel key was pressed with nothing held, to abandon transcriptions in flight.
+    CancelProcessing,
+}
+
+// Tracks which binding, if any, is currently held and turns raw key events into
+// press/release transitions for the platform monitors.
+pub struct HotkeyMonitor {
+    bindings: Vec<(Vec<Key>, HotkeyAction)>,
+    cancel_keys: Vec<Key>,
+    // Set for an evdev trigger, so reloading settings keeps it.
+    trigger: Option<u16>,
+    active: Option<usize>,
+    // Whether the active binding's key is still down.
+    held: bool,
+    // A binding ignored until its key is released, after a cancel or a toggle stop.
+    suppressed: Option<usize>,
+    // Press to start and press again to stop, instead of holding.
+    toggle: bool,
+    last_action_time: Instant,
+    generation: u64,
+}
+
+impl HotkeyMonitor {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mut bindings = Vec::new();
+        for binding in &settings.hotkey_bindings {
+            match parse_key(&binding.key) {
+                Some(keys) => bindings.push((keys, binding.action.clone())),
+                None => eprintln!("Ignoring hotkey binding with unknown key '{}'", binding.key),
+            }
+        }
+        if bindings.is_empty() {
+            let keys = parse_key(&settings.hotkey).unwrap_or_else(|| {
+                eprintln!("Unknown hotkey '{}', using {}", settings.hotkey, default_hotkey());
+                parse_key(default_hotkey()).unwrap_or_default()
+            });
+            bindings.push((keys, HotkeyAction::default()));
+        }
+        let cancel_keys = if settings.cancel_key.trim().is_empty() {
+            Vec::new()
+        } else {
+            parse_key(&settings.cancel_key).unwrap_or_else(|| {
+                eprintln!("Ignoring unknown cancel key '{}'", settings.cancel_key);
+                Vec::new()
+            })
+        };
+        Self::new(bindings, cancel_keys, None, settings)
+    }
+
+    // A monitor for a single evdev trigger, fed by the Linux HID monitor through `trigger_key`.
+    pub fn for_trigger(code: u16, settings: &Settings) -> Self {
+        let action = settings.hid_trigger.as_ref().map(|trigger| trigger.action.clone()).unwrap_or_default();
+        Self::new(vec![(vec![trigger_key(code)], action)], Vec::new(), Some(code), settings)
+    }
+
+    fn new(bindings: Vec<(Vec<Key>, HotkeyAction)>, cancel_keys: Vec<Key>, trigger: Option<u16>, settings: &Settings) -> Self {
+        Self {
+            bindings,
+            cancel_keys,
+            trigger,
+            active: None,
+            held: false,
+            suppressed: None,
+            toggle: settings.recording_mode == RecordingMode::Toggle,
+            last_action_time: Instant::now(),
+            generation: SETTINGS_GENERATION.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn handle(&mut self, event: &EventType) -> Option<HotkeyEvent> {
+        // Waits until nothing is held, so a binding's release is never lost mid-press.
+        if self.active.is_none() && self.suppressed.is_none() && self.generation != SETTINGS_GENERATION.load(Ordering::SeqCst) {
+            let settings = crate::settings::get_settings().unwrap_or_default();
+            *self = match self.trigger {
+                Some(code) => Self::for_trigger(code, &settings),
+                None => Self::from_settings(&settings),
+            };
+        }
+        // The recording was stopped without its key, so its release or toggle press is dropped.
+        if let Some(index) = self.active.filter(|&index| !self.bindings[index].1.continuous) {
+            if !KEY_SESSION.load(Ordering::SeqCst) {
+                self.active = None;
+                self.suppressed = Some(index);
+            }
+        }
+        let now = Instant::now();
+        match event {
+            // The cancelled binding is ignored, auto-repeat included, until it is released.
+            EventType::KeyPress(key) if self.active.is_some() && self.cancel_keys.contains(key) => {
+                let held = self.held;
+                self.suppressed = self.active.take().filter(|_| held);
+                KEY_SESSION.store(false, Ordering::SeqCst);
+                Some(HotkeyEvent::Cancelled)
+            }
+            EventType::KeyPress(key) if self.active.is_none() && self.cancel_keys.contains(key) => {
+                Some(HotkeyEvent::CancelProcessing)
+            }
+            EventType::KeyPress(key) if self.active.is_none() && self.suppressed.is_none() => {
+                let index = self.bindings.iter().position(|(keys, _)| keys.contains(key))?;
+                if now.duration_since(self.last_action_time) <= DEBOUNCE {
+                    return None;
+                }
+                self.last_action_time = now;
+                self.active = Some(index);
+                self.held = true;
+                if !self.bindings[index].1.continuous {
+                    KEY_SESSION.store(true, Ordering::SeqCst);
+                }
+                Some(HotkeyEvent::Pressed(self.bindings[index].1.clone()))
+            }
+            // In toggle mode, pressing the recording binding again stops it.
+            EventType::KeyPress(key) if !self.held => {
+                let index = self.active?;
+                if !self.bindings[index].0.contains(key) || now.duration_since(self.last_action_time) <= DEBOUNCE {
+                    return None;
+                }
+                self.last_action_time = now;
+                self.active = None;
+                self.suppressed = Some(index);
+                KEY_SESSION.store(false, Ordering::SeqCst);
+                Some(HotkeyEvent::Released(self.bindings[index].1.clone()))
+            }
+            EventType::KeyRelease(key) if self.suppressed.is_some() => {
+                if self.suppressed.is_some_and(|index| self.bindings[index].0.contains(key)) {
+                    self.suppressed = None;
+                    self.last_action_time = now;
+                }
+                None
+            }
+            EventType::KeyRelease(key) => {
+                let index = self.active?;
+                if !self.bindings[index].0.contains(key) {
+                    return None;
+                }
+                // Continuous bindings already toggle on their own presses.
+                if self.toggle && !self.bindings[index].1.continuous {
+                    self.held = false;
+                    return None;
+                }
+                self.last_action_time = now;
+                self.active = None;
+                KEY_SESSION.store(false, Ordering::SeqCst);
+                Some(HotkeyEvent::Released(self.bindings[index].1.clone()))
+            }
+            _ => None,
+        }
+    }
+}
@@ -19,3 +19,6 @@ pub fn run() {
 
 pub mod history;
 pub mod update;
+pub mod storage;
+pub mod network;
+pub mod encryption;
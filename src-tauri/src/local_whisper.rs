@@ -0,0 +1,103 @@
+use std::path::Path;
+use std::time::Instant;
+use crate::history::Segment;
+use crate::transcription::{TranscriptionOptions, TranscriptionResult};
+
+// Offline transcription with whisper.cpp through whisper-rs. `local_model_path` points at a
+// ggml model file such as ggml-base.en.bin from the whisper.cpp releases. Builds without
+// the `local-whisper` feature report that instead of transcribing.
+
+#[cfg(feature = "local-whisper")]
+mod engine {
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+    use crate::audio::sample_scale;
+    use crate::history::Segment;
+    use crate::transcription::TranscriptionOptions;
+
+    // Loading a model takes seconds, so the last one used stays in memory.
+    static CONTEXT: Mutex<Option<(PathBuf, WhisperContext)>> = Mutex::new(None);
+
+    fn read_samples(file_path: &Path) -> Result<Vec<f32>, String> {
+        let mut reader = hound::WavReader::open(file_path).map_err(|e| e.to_string())?;
+        let scale = sample_scale(reader.spec().bits_per_sample);
+        reader
+            .samples::<i32>()
+            .map(|sample| sample.map(|s| s as f32 / scale).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    pub fn transcribe(
+        file_path: &Path,
+        model_path: &Path,
+        options: &TranscriptionOptions,
+    ) -> Result<(String, Vec<Segment>), String> {
+        let samples = read_samples(file_path)?;
+        let mut context = CONTEXT.lock().unwrap();
+        if context.as_ref().map(|(path, _)| path.as_path()) != Some(model_path) {
+            let path = model_path.to_str().ok_or("Model path is not valid UTF-8")?;
+            let loaded = WhisperContext::new_with_params(path, WhisperContextParameters::default())
+                .map_err(|e| format!("Failed to load whisper model: {}", e))?;
+            *context = Some((model_path.to_path_buf(), loaded));
+        }
+        let (_, ctx) = context.as_ref().unwrap();
+        let mut state = ctx.create_state().map_err(|e| e.to_string())?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(options.language.as_deref().unwrap_or("auto")));
+        params.set_translate(options.translate);
+        if let Some(prompt) = &options.prompt {
+            params.set_initial_prompt(prompt);
+        }
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        state.full(params, &samples).map_err(|e| e.to_string())?;
+
+        let mut segments = Vec::new();
+        for i in 0..state.full_n_segments().map_err(|e| e.to_string())? {
+            // whisper.cpp timestamps are in hundredths of a second.
+            segments.push(Segment {
+                start: state.full_get_segment_t0(i).map_err(|e| e.to_string())? as f64 / 100.0,
+                end: state.full_get_segment_t1(i).map_err(|e| e.to_string())? as f64 / 100.0,
+                text: state.full_get_segment_text(i).map_err(|e| e.to_string())?.trim().to_string(),
+            });
+        }
+        let text = segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+        Ok((text, segments))
+    }
+}
+
+pub fn transcribe(file_path: &Path, options: &TranscriptionOptions) -> TranscriptionResult {
+    let start = Instant::now();
+    let elapsed_ms = || start.elapsed().as_millis() as u64;
+    let model_path = match options.local_model_path.as_deref() {
+        Some(path) if path.is_file() => path,
+        Some(path) => return TranscriptionResult::error(0, format!("Whisper model not found at {}", path.display())),
+        None => return TranscriptionResult::error(0, "No local whisper model configured".to_string()),
+    };
+
+    #[cfg(feature = "local-whisper")]
+    let result: Result<(String, Vec<Segment>), String> = engine::transcribe(file_path, model_path, options);
+    #[cfg(not(feature = "local-whisper"))]
+    let result: Result<(String, Vec<Segment>), String> = {
+        let _ = (file_path, model_path);
+        Err("This build doesn't include local transcription (the local-whisper feature)".to_string())
+    };
+
+    match result {
+        Ok((text, segments)) => TranscriptionResult {
+            text,
+            round_trip_ms: elapsed_ms(),
+            status: "success".to_string(),
+            error: None,
+            segments: options.segments.then_some(segments),
+            words: None,
+            language: None,
+            retry_after: None,
+            transient: false,
+        },
+        Err(e) => TranscriptionResult::error(elapsed_ms(), e),
+    }
+}
@@ -1,56 +1,603 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::{command, Emitter, Manager, PhysicalPosition};
+use tauri::{command, AppHandle, Emitter, Manager, PhysicalPosition, Runtime};
 mod platform {
+    pub mod dispatch;
     #[cfg(target_os = "windows")]
     pub mod windows;
     #[cfg(target_os = "macos")]
     pub mod macos;
+    #[cfg(target_os = "linux")]
+    pub mod linux;
 }
 mod audio;
 mod tray;
 mod settings;
 mod transcription;
+mod local_whisper;
 mod textinjection;
 mod history;
 mod update;
+mod storage;
+mod usage_stats;
+mod hotkey;
+mod diagnostics;
+mod preprocessing;
+mod queue;
+mod telemetry;
+mod hid;
+mod continuous;
+mod splitter;
+mod postprocess;
+mod network;
+mod api;
+mod export;
+mod encryption;
 use history::{History, TranscriptionEntry};
 use chrono::Utc;
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex, OnceLock};
 use uuid::Uuid;
 use std::fs;
 use base64;
-use dirs::config_dir;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 static HISTORY: OnceLock<History> = OnceLock::new();
+static PROCESSING_JOBS: AtomicUsize = AtomicUsize::new(0);
+// Upper bound on how long quitting waits for in-flight transcriptions.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
-pub fn handle_stop_recording_workflow(app: &tauri::AppHandle, restore_focus: Option<Box<dyn FnOnce()>>, hold_time_ms: Option<u64>) -> Result<(), String> {
+// Held by each recording from submission until its job finishes, on whichever thread
+// ends up running it, so PROCESSING_JOBS only reaches zero once the work is really done.
+struct ProcessingGuard;
+
+impl ProcessingGuard {
+    fn new() -> Self {
+        PROCESSING_JOBS.fetch_add(1, Ordering::SeqCst);
+        ProcessingGuard
+    }
+}
+
+impl Drop for ProcessingGuard {
+    fn drop(&mut self) {
+        PROCESSING_JOBS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn is_processing() -> bool {
+    PROCESSING_JOBS.load(Ordering::SeqCst) > 0
+}
+
+pub struct ProcessingJob {
+    pub hold_time_ms: Option<u64>,
+    pub action: hotkey::HotkeyAction,
+    // Preview jobs report the transcript to the dashboard without injecting or saving it.
+    pub preview: bool,
+    // Settings as they were when recording started, so edits made meanwhile don't affect this job.
+    pub settings: settings::Settings,
+    // Jobs started through the local API hand the transcript back here instead of injecting it.
+    pub reply: Option<std::sync::mpsc::Sender<Result<String, String>>>,
+}
+
+impl ProcessingJob {
+    pub fn new(action: hotkey::HotkeyAction) -> Self {
+        let settings = settings::get_settings().unwrap_or_default();
+        let action = match settings.active_preset() {
+            Some(preset) => action.with_defaults(&preset.action),
+            None => action,
+        };
+        Self {
+            hold_time_ms: None,
+            action,
+            preview: false,
+            settings,
+            reply: None,
+        }
+    }
+
+    // A hand-edited settings file may name a model Groq doesn't serve. Other servers have
+    // their own model names, which are passed through as configured.
+    fn default_model(&self) -> String {
+        let model = &self.settings.groq_model;
+        let is_groq = self.settings.transcription_base_url == transcription::DEFAULT_BASE_URL;
+        if !is_groq || transcription::MODELS.contains(&model.as_str()) {
+            return model.clone();
+        }
+        eprintln!("Unknown model '{}' in settings, using {}", model, transcription::DEFAULT_MODEL);
+        transcription::DEFAULT_MODEL.to_string()
+    }
+
+    fn transcription_options(&self) -> transcription::TranscriptionOptions {
+        transcription::TranscriptionOptions {
+            model: self.action.model.clone().unwrap_or_else(|| self.default_model()),
+            language: self.action.language.clone().or_else(|| self.settings.preferred_language.clone()),
+            translate: self.action.translate.unwrap_or(self.settings.translate_to_english),
+            segments: self.settings.response_detail != settings::ResponseDetail::Text,
+            words: self.settings.response_detail == settings::ResponseDetail::Words,
+            prompt: self.action.prompt.clone().or_else(|| self.settings.transcription_prompt.clone()),
+            network: self.settings.network.clone(),
+            generation: transcription::current_generation(),
+            base_url: self.settings.transcription_base_url.clone(),
+            backend: self.settings.transcription_backend,
+            local_model_path: self.settings.local_model_path.clone(),
+        }
+    }
+}
+
+static PENDING_JOB: Mutex<Option<ProcessingJob>> = Mutex::new(None);
+// When the last recording was stopped and handed off for transcription.
+static LAST_SESSION_END: Mutex<Option<Instant>> = Mutex::new(None);
+
+// A release followed by an immediate re-press can start the next recording before the
+// previous one has been stopped and handed off, which would mix the two captures. New
+// sessions wait for that hand-off and then for the configured cooldown.
+fn wait_for_session_cooldown(cooldown: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + cooldown;
+    let mut engaged = false;
+    while audio::is_recording() {
+        if Instant::now() >= deadline {
+            return Err("The previous recording is still being stopped".to_string());
+        }
+        engaged = true;
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let since_end = LAST_SESSION_END.lock().unwrap().map(|end| end.elapsed());
+    if let Some(remaining) = since_end.and_then(|since_end| cooldown.checked_sub(since_end)) {
+        engaged = true;
+        std::thread::sleep(remaining);
+    }
+    if engaged {
+        eprintln!("Session cooldown engaged before starting a new recording");
+    }
+    Ok(())
+}
+
+// Starts recording and remembers the job it belongs to until the recording is stopped.
+pub fn start_session(job: ProcessingJob) -> Result<(), String> {
+    wait_for_session_cooldown(Duration::from_millis(job.settings.session_cooldown_ms))?;
+    let settings = &job.settings;
+    audio::start_recording(
+        settings.effective_upload_format().bits_per_sample(),
+        settings.preroll_ms,
+        settings.max_recording_secs,
+        settings.volume_threshold,
+    )
+    .map_err(|e| e.to_string())?;
+    *PENDING_JOB.lock().unwrap() = Some(job);
+    Ok(())
+}
+
+// Stops the current recording and throws it away without transcribing it.
+pub fn cancel_session() {
+    if let Err(e) = audio::stop_recording() {
+        eprintln!("Failed to stop recording: {}", e);
+    }
+    PENDING_JOB.lock().unwrap().take();
+    let _ = fs::remove_file(std::env::temp_dir().join("vwisper_audio_latest.wav"));
+    *LAST_SESSION_END.lock().unwrap() = Some(Instant::now());
+}
+
+pub fn take_pending_job() -> ProcessingJob {
+    take_started_job().unwrap_or_else(|| ProcessingJob::new(hotkey::HotkeyAction::default()))
+}
+
+// None when no recording was started, e.g. because starting it failed.
+pub fn take_started_job() -> Option<ProcessingJob> {
+    PENDING_JOB.lock().unwrap().take()
+}
+
+type SessionTask = Box<dyn FnOnce() + Send>;
+static SESSION_TASKS: OnceLock<Mutex<std::sync::mpsc::Sender<SessionTask>>> = OnceLock::new();
+
+// Key hooks must return quickly or the OS removes them, and starting a session can wait
+// out the cooldown, so the key monitors queue starts and stops to run here in order.
+pub fn queue_session_task(task: impl FnOnce() + Send + 'static) {
+    let tasks = SESSION_TASKS.get_or_init(|| {
+        let (sender, receiver) = std::sync::mpsc::channel::<SessionTask>();
+        std::thread::spawn(move || {
+            for task in receiver {
+                task();
+            }
+        });
+        Mutex::new(sender)
+    });
+    let _ = tasks.lock().unwrap().send(Box::new(task));
+}
+
+// Stops the recording started for `job` and shows its progress on the pill until the
+// transcript has been delivered.
+pub fn finish_session(app: &tauri::AppHandle, restore_focus: Option<Box<dyn FnOnce() + Send>>, job: ProcessingJob) {
+    let _ = app.emit_to("main", "pill-state", "loading");
+    let _ = app.emit_to("main", "stop-recording", "");
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = handle_stop_recording_workflow(&app, restore_focus, job) {
+            eprintln!("Error in handle_stop_recording_workflow: {}", e);
+            let _ = app.emit_to("main", "pill-state", "error");
+            std::thread::sleep(Duration::from_secs(3));
+        } else {
+            let _ = app.emit_to("main", "pill-state", "success");
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        let _ = app.emit_to("main", "pill-state", "idle");
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.hide();
+        }
+    });
+}
+
+// Hands off a push-to-talk or toggle recording as if its key had been released, e.g. once
+// it reaches max_recording_secs, so the rest of the session isn't silently dropped.
+pub fn stop_key_session(app: &tauri::AppHandle) {
+    let app = app.clone();
+    hand_off_key_session(move |job| finish_session(&app, None, job));
+}
+
+fn hand_off_key_session(submit: impl FnOnce(ProcessingJob) + Send + 'static) {
+    // Sessions started elsewhere, e.g. continuous dictation, handle the limit themselves.
+    if !hotkey::end_key_session() {
+        return;
+    }
+    queue_session_task(move || {
+        if let Some(job) = take_started_job() {
+            submit(job);
+        }
+    });
+}
+
+// Collapses the pill if a job hasn't finished within the timeout, so a hung request or
+// stuck worker can't leave it spinning forever. Dropping the watchdog disarms it.
+struct ProcessingWatchdog(Arc<AtomicBool>);
+
+impl ProcessingWatchdog {
+    fn arm(app: &tauri::AppHandle, timeout: Duration) -> Self {
+        let finished = Arc::new(AtomicBool::new(false));
+        let watched = finished.clone();
+        let app = app.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            if watched.load(Ordering::SeqCst) {
+                return;
+            }
+            eprintln!("Processing did not finish within {:?}, resetting the pill", timeout);
+            let _ = app.emit("processing-timed-out", timeout.as_secs());
+            let _ = app.emit_to("main", "pill-state", "idle");
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.hide();
+            }
+        });
+        ProcessingWatchdog(finished)
+    }
+}
+
+impl Drop for ProcessingWatchdog {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn handle_stop_recording_workflow(app: &tauri::AppHandle, restore_focus: Option<Box<dyn FnOnce() + Send>>, job: ProcessingJob) -> Result<(), String> {
+    let timeout = job.settings.processing_timeout_secs;
+    let _watchdog = (!job.preview && timeout > 0)
+        .then(|| ProcessingWatchdog::arm(app, Duration::from_secs(timeout)));
+    let done = submit_recording(app, restore_focus, job)?;
+    done.recv().map_err(|_| "Transcription job was dropped".to_string())
+}
+
+// Stops the recording and queues it for transcription. Returns once the audio has been
+// handed off, so a new recording can start while this one is still being transcribed;
+// the receiver fires when the job has finished.
+pub fn submit_recording(app: &tauri::AppHandle, restore_focus: Option<Box<dyn FnOnce() + Send>>, job: ProcessingJob) -> Result<std::sync::mpsc::Receiver<()>, String> {
+    queue_recording(app, restore_focus, job).map_err(|e| {
+        report_injection(app, InjectionCompleted::skipped("not_submitted", Some(e.clone())));
+        e
+    })
+}
+
+fn queue_recording(app: &tauri::AppHandle, restore_focus: Option<Box<dyn FnOnce() + Send>>, job: ProcessingJob) -> Result<std::sync::mpsc::Receiver<()>, String> {
+    let processing = ProcessingGuard::new();
     audio::stop_recording().map_err(|e| e.to_string())?;
+    let audio_stats = audio::last_recording_stats();
     
-    let settings = settings::get_settings().map_err(|e| e.to_string())?;
-    let api_key = settings.groq_api_key.ok_or("No Groq API key set")?;
-    let temp_dir = std::env::temp_dir();
-    let file_path = temp_dir.join("vwisper_audio_latest.wav");
+    let api_key = job.settings.api_key()?;
+    // The capture file is reused by the next recording, so each queued job gets its own copy.
     let id = Uuid::new_v4().to_string();
+    let file_path = std::env::temp_dir().join(format!("vwisper_job_{}.wav", id));
+    fs::rename(std::env::temp_dir().join("vwisper_audio_latest.wav"), &file_path)
+        .map_err(|e| format!("Failed to take recording: {}", e))?;
+    *LAST_SESSION_END.lock().unwrap() = Some(Instant::now());
+
+    let max_concurrent = job.settings.max_concurrent_jobs;
+    let max_depth = job.settings.max_queue_depth;
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    let task_app = app.clone();
+    let recording = Recording { id, file_path: file_path.clone(), audio_stats };
+    let submitted = queue::submit(Box::new(move |ticket| {
+        let _processing = processing;
+        process_recording(&task_app, restore_focus, job, ticket, recording, &api_key);
+        let _ = done_tx.send(());
+    }), max_concurrent, max_depth);
+    if let Err(e) = submitted {
+        eprintln!("{}", e);
+        let _ = fs::remove_file(&file_path);
+        let _ = app.emit_to("main", "transcription-error", &e);
+        return Err(e);
+    }
+    Ok(done_rx)
+}
+
+// Rate-limited and transiently failed requests are retried in place rather than requeued,
+// which keeps the job's place in delivery order. Both limits bound how long a persistent
+// failure can stall it.
+const MAX_RETRIES: u32 = 3;
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(30);
+// Doubled after each failed attempt.
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone, serde::Serialize)]
+struct ThrottleNotice {
+    retry_in_secs: u64,
+    attempt: u32,
+}
+
+// Adds a timestamped line to the notes file. The line goes out in a single append-mode
+// write, so concurrent writers can't interleave within it.
+fn append_transcript(path: &std::path::Path, text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let line = format!("[{}] {}\n", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), text);
+    fs::OpenOptions::new().create(true).append(true).open(path)?.write_all(line.as_bytes())
+}
+
+// Sent once injection has finished, including the optional Return press, or once it's
+// clear that nothing will be injected for a recording.
+#[derive(Clone, serde::Serialize)]
+struct InjectionCompleted {
+    success: bool,
+    chars: usize,
+    error: Option<String>,
+    // Why nothing was injected, e.g. "secure_input" or "no_text".
+    reason: Option<&'static str>,
+}
+
+impl InjectionCompleted {
+    fn skipped(reason: &'static str, error: Option<String>) -> Self {
+        InjectionCompleted { success: false, chars: 0, error, reason: Some(reason) }
+    }
+}
+
+static LAST_INJECTION: Mutex<Option<InjectionCompleted>> = Mutex::new(None);
+
+fn report_injection(app: &tauri::AppHandle, completed: InjectionCompleted) {
+    let _ = app.emit("injection-completed", &completed);
+    *LAST_INJECTION.lock().unwrap() = Some(completed);
+}
+
+#[command]
+fn get_last_injection() -> Option<InjectionCompleted> {
+    LAST_INJECTION.lock().unwrap().clone()
+}
+
+fn transcribe_with_retries(
+    app: &tauri::AppHandle,
+    file_path: &std::path::Path,
+    api_key: &str,
+    options: &transcription::TranscriptionOptions,
+) -> transcription::TranscriptionResult {
+    retry_transcription(
+        || transcription::transcribe_audio(file_path.to_str().unwrap(), api_key, options),
+        options,
+        std::thread::sleep,
+        |result, notice| {
+            if result.retry_after.is_some() {
+                eprintln!("Rate limited by Groq, retrying in {}s (attempt {})", notice.retry_in_secs, notice.attempt);
+                let _ = app.emit("transcription-throttled", notice);
+            } else {
+                eprintln!("Transcription failed ({}), retrying in {}s (attempt {})", result.error.as_deref().unwrap_or("unknown error"), notice.retry_in_secs, notice.attempt);
+                let _ = app.emit("transcription-retrying", notice);
+            }
+        },
+    )
+}
+
+// The retry policy behind transcribe_with_retries, with waiting and reporting passed in
+// so it can be exercised without sleeping.
+fn retry_transcription(
+    mut transcribe: impl FnMut() -> transcription::TranscriptionResult,
+    options: &transcription::TranscriptionOptions,
+    mut wait: impl FnMut(Duration),
+    mut notify: impl FnMut(&transcription::TranscriptionResult, ThrottleNotice),
+) -> transcription::TranscriptionResult {
+    let mut waited = Duration::ZERO;
+    let mut attempt = 0;
+    loop {
+        let result = transcribe();
+        if options.is_cancelled() {
+            return result;
+        }
+        let delay = match result.retry_after {
+            Some(delay) => delay,
+            None if result.transient => TRANSIENT_RETRY_DELAY * 2u32.pow(attempt),
+            None => return result,
+        };
+        if attempt >= MAX_RETRIES || waited + delay > MAX_RETRY_WAIT {
+            return result;
+        }
+        attempt += 1;
+        waited += delay;
+        notify(&result, ThrottleNotice {
+            retry_in_secs: delay.as_secs_f64().ceil() as u64,
+            attempt,
+        });
+        wait(delay);
+    }
+}
+
+// Long recordings are optionally sent as several requests and stitched back together.
+fn transcribe_recording(
+    app: &tauri::AppHandle,
+    file_path: &std::path::Path,
+    api_key: &str,
+    options: &transcription::TranscriptionOptions,
+    max_segment_secs: u64,
+) -> transcription::TranscriptionResult {
+    let parts = match splitter::split_wav_file(file_path, max_segment_secs) {
+        Ok(Some(parts)) => parts,
+        Ok(None) => return transcribe_with_retries(app, file_path, api_key, options),
+        Err(e) => {
+            eprintln!("Failed to split recording, sending it whole: {}", e);
+            return transcribe_with_retries(app, file_path, api_key, options);
+        }
+    };
+
+    let mut texts = Vec::new();
+    let mut language = None;
+    let mut segments: Option<Vec<history::Segment>> = None;
+    let mut words: Option<Vec<history::Segment>> = None;
+    let mut round_trip_ms = 0;
+    let mut failure = None;
+    for part in &parts {
+        if failure.is_none() {
+            let result = transcribe_with_retries(app, &part.path, api_key, options);
+            round_trip_ms += result.round_trip_ms;
+            if result.status == "success" {
+                if !result.text.is_empty() {
+                    texts.push(result.text);
+                }
+                language = language.or(result.language);
+                let offset = |segment: history::Segment| history::Segment {
+                    start: segment.start + part.start_secs,
+                    end: segment.end + part.start_secs,
+                    text: segment.text,
+                };
+                if let Some(part_segments) = result.segments {
+                    segments.get_or_insert_with(Vec::new).extend(part_segments.into_iter().map(offset));
+                }
+                if let Some(part_words) = result.words {
+                    words.get_or_insert_with(Vec::new).extend(part_words.into_iter().map(offset));
+                }
+            } else {
+                failure = Some(result.error.unwrap_or_else(|| "Transcription failed".to_string()));
+            }
+        }
+        let _ = fs::remove_file(&part.path);
+    }
+
+    match failure {
+        Some(error) => transcription::TranscriptionResult::error(round_trip_ms, error),
+        None => transcription::TranscriptionResult {
+            text: texts.join(" "),
+            round_trip_ms,
+            status: "success".to_string(),
+            error: None,
+            segments,
+            words,
+            language,
+            retry_after: None,
+            transient: false,
+        },
+    }
+}
+
+// Kept short so a notification on a shared screen reveals little of what was dictated.
+const NOTIFICATION_PREVIEW_CHARS: usize = 30;
+
+fn notify_success(app: &tauri::AppHandle, text: &str) {
+    use tauri_plugin_notification::NotificationExt;
+
+    let result = app
+        .notification()
+        .builder()
+        .title("VWisper")
+        .body(history::preview(text, NOTIFICATION_PREVIEW_CHARS))
+        .show();
+    if let Err(e) = result {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+const OWN_FOCUS_RETRY_DELAY: Duration = Duration::from_millis(300);
+
+struct Recording {
+    id: String,
+    file_path: std::path::PathBuf,
+    audio_stats: Option<history::AudioStats>,
+}
+
+fn process_recording(
+    app: &tauri::AppHandle,
+    restore_focus: Option<Box<dyn FnOnce() + Send>>,
+    job: ProcessingJob,
+    ticket: queue::Ticket,
+    recording: Recording,
+    api_key: &str,
+) {
+    let Recording { id, file_path, audio_stats } = recording;
+    let file_path = file_path.as_path();
+    let action = &job.action;
+    let settings = &job.settings;
+    if settings.preprocessing.is_enabled() {
+        if let Err(e) = preprocessing::process_wav_file(file_path, &settings.preprocessing) {
+            eprintln!("Audio preprocessing failed, sending the raw recording: {}", e);
+        }
+    }
     let mut wav_path = None;
-    if settings.save_history && settings.save_audio {
-        // Save audio to audio_out/{id}.wav in config dir
-        if let Some(mut config_path) = config_dir() {
-            config_path.push("vwisper");
-            config_path.push("audio_out");
-            std::fs::create_dir_all(&config_path).ok();
-            let out_path = config_path.join(format!("{}.wav", id));
-            std::fs::copy(&file_path, &out_path).ok();
-            wav_path = Some(out_path.to_string_lossy().to_string());
+    if !job.preview && settings.save_history && settings.save_audio {
+        // Save audio to audio_out/{id}.wav in the app data dir
+        let out_path = storage::data_subdir("audio_out").join(format!("{}.wav", id));
+        std::fs::copy(file_path, &out_path).ok();
+        wav_path = Some(out_path.to_string_lossy().to_string());
+    }
+    
+    let options = job.transcription_options();
+    let mut result = transcribe_recording(app, file_path, api_key, &options, settings.max_segment_secs);
+    if result.status == "success" && postprocess::is_hallucination(&result.text, &settings.hallucination_denylist) {
+        eprintln!("Dropping transcript that matches the hallucination denylist: {:?}", result.text);
+        result.text.clear();
+    }
+    if action.code_mode && result.status == "success" {
+        result.text = postprocess::apply_spoken_commands(&result.text, &settings.spoken_commands);
+    }
+    let _ = std::fs::remove_file(file_path);
+    // Parallel transcriptions still reach the user in the order they were dictated.
+    ticket.wait_turn();
+
+    // Nothing is typed or recorded once the user has given up on the job.
+    if options.is_cancelled() {
+        eprintln!("Transcription cancelled, discarding the result");
+        if let Some(path) = &wav_path {
+            let _ = std::fs::remove_file(path);
+        }
+        let _ = app.emit("transcription-cancelled", ());
+        if let Some(reply) = &job.reply {
+            let _ = reply.send(Err(transcription::CANCELLED_ERROR.to_string()));
         }
+        report_injection(app, InjectionCompleted::skipped("cancelled", None));
+        return;
     }
     
-    let result = transcription::transcribe_audio(file_path.to_str().unwrap(), &api_key);
+    if job.preview {
+        let _ = app.emit("transcription-preview", &result);
+        report_injection(app, InjectionCompleted::skipped("preview", None));
+        return;
+    }
     
     if result.status == "success" && !result.text.is_empty() {
         let _ = app.emit_to("main", "transcription-result", &result.text);
+        if let Some(segments) = &result.segments {
+            let _ = app.emit("transcription-segments", segments);
+        }
+        if let Some(language) = &result.language {
+            let _ = app.emit("detected-language", language);
+        }
+        if let Some(path) = &settings.append_to_file {
+            if let Err(e) = append_transcript(path, &result.text) {
+                eprintln!("Failed to append transcript to {}: {}", path.display(), e);
+            }
+        }
         
         // Restore focus to the original window before injecting text
         if let Some(restore_fn) = restore_focus {
@@ -59,20 +606,68 @@ pub fn handle_stop_recording_workflow(app: &tauri::AppHandle, restore_focus: Opt
             std::thread::sleep(Duration::from_millis(100));
         }
         
-        match textinjection::inject_text(&result.text) {
-            Ok(_) => {
-                let _ = app.emit_to("main", "injection-status", "success");
-            }
-            Err(e) => {
-                eprintln!("Text injection failed: {}", e);
-                let _ = app.emit_to("main", "injection-status", "error");
-                let _ = app.emit_to("main", "injection-error", &e.to_string());
+        // Focus restoration can lose the race against our own windows on some window managers.
+        if job.reply.is_none() && textinjection::own_window_focused() {
+            std::thread::sleep(OWN_FOCUS_RETRY_DELAY);
+        }
+        if let Some(reply) = &job.reply {
+            let _ = reply.send(Ok(result.text.clone()));
+            report_injection(app, InjectionCompleted::skipped("api_reply", None));
+        } else if textinjection::own_window_focused() {
+            eprintln!("Not injecting: a VWisper window has focus");
+            let message = "A VWisper window has focus; click into the target field and try again";
+            let _ = app.emit_to("main", "injection-status", "error");
+            let _ = app.emit_to("main", "injection-error", message);
+            report_injection(app, InjectionCompleted::skipped("own_window_focused", Some(message.to_string())));
+        } else if settings.block_secure_injection && textinjection::secure_input_active() {
+            eprintln!("Not injecting: a password field appears to have focus");
+            let _ = app.emit_to("main", "injection-blocked", "secure_input");
+            report_injection(app, InjectionCompleted::skipped("secure_input", None));
+        } else {
+            match textinjection::inject_text(&result.text, &settings.injection_fallback_chain) {
+                Ok(_) => {
+                    let inject_submit = action.inject_submit.unwrap_or(settings.inject_submit);
+                    if inject_submit.applies(&settings.submit_apps, textinjection::focused_app_name) {
+                        if let Err(e) = textinjection::press_return() {
+                            eprintln!("Failed to submit after injection: {}", e);
+                        }
+                    }
+                    let _ = app.emit_to("main", "injection-status", "success");
+                    report_injection(app, InjectionCompleted {
+                        success: true,
+                        chars: result.text.chars().count(),
+                        error: None,
+                        reason: None,
+                    });
+                    if settings.notify_on_success {
+                        notify_success(app, &result.text);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Text injection failed: {}", e);
+                    let _ = app.emit_to("main", "injection-status", "error");
+                    let _ = app.emit_to("main", "injection-error", &e.to_string());
+                    report_injection(app, InjectionCompleted {
+                        success: false,
+                        chars: 0,
+                        error: Some(e.to_string()),
+                        reason: Some("injection_failed"),
+                    });
+                }
             }
         }
     } else {
-        let error_msg = result.error.unwrap_or_else(|| "Transcription failed".to_string());
+        let error_msg = result.error.clone().unwrap_or_else(|| {
+            if result.status == "success" { "No speech detected" } else { "Transcription failed" }.to_string()
+        });
         eprintln!("Transcription failed: {}", error_msg);
+        telemetry::record_error(telemetry::error_category(&error_msg));
         let _ = app.emit_to("main", "transcription-error", &error_msg);
+        let reason = if result.status == "success" { "no_text" } else { "transcription_failed" };
+        report_injection(app, InjectionCompleted::skipped(reason, Some(error_msg.clone())));
+        if let Some(reply) = &job.reply {
+            let _ = reply.send(Err(error_msg));
+        }
     }
     
     if settings.save_history {
@@ -82,18 +677,53 @@ pub fn handle_stop_recording_workflow(app: &tauri::AppHandle, restore_focus: Opt
             timestamp: Utc::now(),
             text: result.text.clone(),
             round_trip_ms: Some(result.round_trip_ms),
-            hold_time_ms: hold_time_ms,
+            hold_time_ms: job.hold_time_ms,
             status: result.status.clone(),
             wav_path,
-        });
-    } else {
-        let _ = std::fs::remove_file(&file_path);
+            audio_stats,
+            segments: result.segments.clone(),
+            words: result.words.clone(),
+            error: result.error.clone(),
+        }, settings.history_max_entries);
+        if let Some(days) = settings.history_retention_days {
+            history.delete_older_than(days);
+        }
+        if settings.max_saved_recordings > 0 {
+            history.prune_audio(settings.max_saved_recordings);
+        }
+        tray::refresh_tray_menu(app);
     }
-    Ok(())
+}
+
+pub fn flush_state() {
+    if let Some(history) = HISTORY.get() {
+        history.flush();
+    }
+    telemetry::flush();
+}
+
+// Stops any recording, waits (bounded) for in-flight transcriptions, flushes state and exits.
+pub fn shutdown<R: Runtime>(app: &AppHandle<R>) {
+    if let Err(e) = audio::stop_recording() {
+        eprintln!("Failed to stop recording during shutdown: {}", e);
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    while is_processing() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    if is_processing() {
+        eprintln!("Quitting with transcriptions still in progress");
+    }
+    flush_state();
+    app.exit(0);
 }
 
 fn main() {
     tauri::Builder::default()
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            tray::show_dashboard(app);
+        }))
+        .plugin(tauri_plugin_notification::init())
         .on_window_event(|window, event| {
             if window.label() == "dashboard" {
                 if let tauri::WindowEvent::CloseRequested { api, .. } = event {
@@ -103,6 +733,15 @@ fn main() {
             }
         })
         .setup(|app| {
+            storage::migrate_legacy_dirs();
+            let saved = settings::get_settings().unwrap_or_default();
+            storage::set_data_dir_override(saved.data_dir_override);
+            history::set_encryption(saved.encrypt_history);
+            if let Some(days) = saved.history_retention_days {
+                HISTORY.get_or_init(History::new).delete_older_than(days);
+            }
+            telemetry::init();
+
             #[cfg(desktop)]
             let _ = app.handle().plugin(tauri_plugin_autostart::init(
                 tauri_plugin_autostart::MacosLauncher::LaunchAgent,
@@ -138,40 +777,136 @@ fn main() {
                 let app_handle = app.handle().clone();
                 platform::macos::start_global_key_monitor(app_handle);
             }
+            let startup_settings = settings::get_settings().unwrap_or_default();
+            if startup_settings.api_enabled {
+                api::start(app.handle().clone(), startup_settings.api_port, startup_settings.api_token.clone());
+            }
+            #[cfg(target_os = "linux")]
+            if let Some(trigger) = startup_settings.hid_trigger {
+                platform::linux::start_hid_monitor(app.handle().clone(), trigger);
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             start_audio_recording,
             stop_audio_recording,
             manual_stop_recording,
+            start_preview_recording,
+            stop_preview_recording,
+            reinit_audio,
+            set_input_device,
+            get_recording_elapsed_ms,
+            cancel_processing,
+            start_mic_test,
+            stop_mic_test,
+            list_input_devices,
+            get_app_state,
             settings::get_settings,
             settings::save_settings,
             settings::reset_settings,
             settings::get_settings_path,
+            settings::open_config_file,
+            settings::open_data_directory,
+            settings::set_data_dir_override,
+            settings::set_hotkey,
+            settings::set_recording_mode,
+            get_last_injection,
+            settings::set_groq_model,
+            settings::set_transcription_prompt,
+            settings::reset_transcription_prompt,
+            settings::set_preferred_language,
+            settings::set_input_gain,
+            settings::list_presets,
+            settings::save_preset,
+            settings::apply_preset,
+            settings::delete_preset,
+            settings::record_language_correction,
             get_transcription_history,
+            search_transcription_history,
+            set_encrypt_history,
+            delete_transcription_entry,
+            clear_transcription_history,
+            get_history_stats,
+            get_history_entries_by_date,
+            get_transcription_history_paged,
+            reload_transcription_history,
+            export_transcription_history,
+            prune_history_now,
+            get_history_preview,
+            retry_failed_sessions,
+            get_formatted_usage_stats,
+            get_productivity_stats,
+            get_estimated_cost,
+            delete_history_between,
+            delete_history_older_than,
             get_audio_base64,
             inject_text_manual,
             get_text_injector_status,
             test_text_injection,
+            diagnostics::run_self_test,
+            diagnostics::benchmark_transcription,
+            diagnostics::cancel_benchmark,
+            telemetry::get_telemetry_status,
+            telemetry::set_telemetry_consent,
+            hid::list_hid_devices,
+            hid::detect_hid_key,
+            hid::set_hid_trigger,
+            continuous::start_continuous_dictation,
+            continuous::stop_continuous_dictation,
             check_for_updates,
-            download_and_install_update
+            download_and_install_update,
+            quit_app
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                flush_state();
+            }
+        });
+}
+
+// Lets a window that opened mid-session, or missed an event, resynchronize its UI.
+#[derive(serde::Serialize)]
+struct AppState {
+    recording: bool,
+    processing: bool,
+    // Recordings submitted but not yet finished, the one in progress included.
+    jobs: usize,
+    queue_depth: usize,
+    continuous: bool,
+    monitoring_enabled: bool,
+}
+
+#[command]
+fn get_app_state() -> AppState {
+    AppState {
+        recording: audio::is_recording(),
+        processing: is_processing(),
+        jobs: PROCESSING_JOBS.load(Ordering::SeqCst),
+        queue_depth: queue::pending_len(),
+        continuous: continuous::is_running(),
+        monitoring_enabled: hotkey::is_monitoring(),
+    }
 }
 
 #[command]
 fn start_audio_recording() -> Result<(), String> {
-    audio::start_recording().map_err(|e| e.to_string())
+    start_session(ProcessingJob::new(hotkey::HotkeyAction::default()))
 }
 
 #[command]
 fn stop_audio_recording(app: tauri::AppHandle) -> Result<(), String> {
-    handle_stop_recording_workflow(&app, None, None)
+    handle_stop_recording_workflow(&app, None, take_pending_job())
 }
 
 #[command]
 fn manual_stop_recording(app: tauri::AppHandle) -> Result<(), String> {
+    if continuous::is_running() {
+        continuous::stop();
+        return Ok(());
+    }
+
     // Check if recording is actually in progress
     if !audio::is_recording() {
         return Ok(());
@@ -186,7 +921,7 @@ fn manual_stop_recording(app: tauri::AppHandle) -> Result<(), String> {
     // Handle the stop recording workflow in a separate thread
     let app_handle_clone = app.clone();
     std::thread::spawn(move || {
-        let result = handle_stop_recording_workflow(&app_handle_clone, None, None);
+        let result = handle_stop_recording_workflow(&app_handle_clone, None, take_pending_job());
         if let Err(e) = result {
             eprintln!("Error in handle_stop_recording_workflow: {}", e);
             let _ = app_handle_clone.emit_to("main", "pill-state", "error");
@@ -202,12 +937,297 @@ fn manual_stop_recording(app: tauri::AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+#[command]
+fn start_preview_recording() -> Result<(), String> {
+    start_session(ProcessingJob {
+        preview: true,
+        ..ProcessingJob::new(hotkey::HotkeyAction::default())
+    })
+}
+
+// The transcript is delivered through the `transcription-preview` event.
+#[command]
+fn stop_preview_recording(app: tauri::AppHandle) -> Result<(), String> {
+    if !audio::is_recording() {
+        return Ok(());
+    }
+    std::thread::spawn(move || {
+        if let Err(e) = handle_stop_recording_workflow(&app, None, take_pending_job()) {
+            eprintln!("Preview transcription failed: {}", e);
+            let _ = app.emit("transcription-preview", transcription::TranscriptionResult::error(0, e));
+        }
+    });
+    Ok(())
+}
+
+// Reopening the stream waits for the capture thread, so it runs off the main thread.
+#[command]
+async fn reinit_audio(app: tauri::AppHandle) -> Result<audio::CaptureInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || audio::reinit_audio_capture(app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+fn list_input_devices() -> Result<Vec<audio::AudioDeviceInfo>, String> {
+    Ok(audio::list_input_devices())
+}
+
+// Switches capture to the named device, or back to the default when `name` is None.
+#[command]
+async fn set_input_device(app: tauri::AppHandle, name: Option<String>) -> Result<(), String> {
+    let name = name.filter(|name| !name.trim().is_empty());
+    settings::update_settings(&app, |settings| settings.input_device_name = name)?;
+    if !audio::is_recording() {
+        return tauri::async_runtime::spawn_blocking(move || audio::reinit_audio_capture(app).map(|_| ()))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    eprintln!("Input device changed during a recording; it takes effect once the recording ends");
+    std::thread::spawn(move || {
+        while audio::is_recording() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        if let Err(e) = audio::reinit_audio_capture(app) {
+            eprintln!("Failed to switch input device: {}", e);
+        }
+    });
+    Ok(())
+}
+
+// Abandons every transcription currently in flight; queued recordings still go through.
+#[command]
+fn cancel_processing() {
+    transcription::cancel_in_flight();
+}
+
+#[command]
+fn get_recording_elapsed_ms() -> u64 {
+    audio::recording_elapsed_ms()
+}
+
+// The capture stream is always open, so a mic test only needs to switch the level events on.
+#[command]
+fn start_mic_test() -> Result<(), String> {
+    if audio::is_recording() {
+        return Err("Can't test the microphone while recording".to_string());
+    }
+    audio::set_mic_test(true);
+    Ok(())
+}
+
+#[command]
+fn stop_mic_test() {
+    audio::set_mic_test(false);
+}
+
 #[command]
 fn get_transcription_history() -> Vec<TranscriptionEntry> {
     let history = HISTORY.get_or_init(History::new);
     history.get_entries()
 }
 
+#[command]
+fn set_encrypt_history(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    if enabled {
+        // Fails early if the keychain can't hold the key, before anything is changed.
+        encryption::encrypt("")?;
+    }
+    settings::update_settings(&app, |settings| settings.encrypt_history = enabled)?;
+    history::set_encryption(enabled);
+    HISTORY.get_or_init(History::new).rewrite();
+    Ok(())
+}
+
+#[command]
+fn search_transcription_history(query: String) -> Vec<TranscriptionEntry> {
+    let history = HISTORY.get_or_init(History::new);
+    history.search(query.trim())
+}
+
+#[command]
+fn delete_transcription_entry(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    let history = HISTORY.get_or_init(History::new);
+    if !history.delete_entry(&id) {
+        return Err(format!("No history entry with id {}", id));
+    }
+    tray::refresh_tray_menu(&app);
+    Ok(())
+}
+
+#[command]
+fn clear_transcription_history(app: tauri::AppHandle) -> usize {
+    let history = HISTORY.get_or_init(History::new);
+    let removed = history.clear();
+    tray::refresh_tray_menu(&app);
+    removed
+}
+
+#[command]
+fn get_history_stats() -> usage_stats::UsageStats {
+    let history = HISTORY.get_or_init(History::new);
+    usage_stats::UsageStats::from_entries(&history.get_entries())
+}
+
+#[command]
+fn export_transcription_history(format: export::ExportFormat, path: String) -> Result<usize, String> {
+    let history = HISTORY.get_or_init(History::new);
+    export::export(&history.get_entries(), format, std::path::Path::new(&path))
+}
+
+fn parse_date(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("Invalid date '{}': {}", value, e))
+}
+
+#[command]
+fn get_transcription_history_paged(
+    offset: usize,
+    limit: usize,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<history::HistoryPage, String> {
+    let from = from.as_deref().map(parse_date).transpose()?;
+    let to = to.as_deref().map(parse_date).transpose()?;
+    let history = HISTORY.get_or_init(History::new);
+    Ok(history.page(offset, limit, from, to))
+}
+
+#[command]
+fn get_history_entries_by_date(date: String) -> Result<Vec<TranscriptionEntry>, String> {
+    let date = parse_date(&date)?;
+    let history = HISTORY.get_or_init(History::new);
+    Ok(history.entries_on(date))
+}
+
+#[command]
+fn reload_transcription_history(app: tauri::AppHandle) -> usize {
+    let history = HISTORY.get_or_init(History::new);
+    let count = history.reload();
+    tray::refresh_tray_menu(&app);
+    count
+}
+
+#[derive(serde::Serialize)]
+struct RetryReport {
+    retried: usize,
+    recovered: usize,
+    // Failed entries whose audio was not saved or has since been deleted.
+    skipped: usize,
+}
+
+// Re-sends the saved audio of failed entries with the current settings and key, and
+// rewrites the entries that now succeed.
+fn retry_failed_entries(app: &tauri::AppHandle) -> Result<RetryReport, String> {
+    let job = ProcessingJob::new(hotkey::HotkeyAction::default());
+    let api_key = job.settings.api_key()?;
+    let options = job.transcription_options();
+    let history = HISTORY.get_or_init(History::new);
+    let mut report = RetryReport { retried: 0, recovered: 0, skipped: 0 };
+    for entry in history.get_entries().into_iter().filter(|entry| entry.status != "success") {
+        let Some(path) = entry.wav_path.as_deref().map(std::path::Path::new).filter(|path| path.exists()) else {
+            report.skipped += 1;
+            continue;
+        };
+        report.retried += 1;
+        let result = transcribe_recording(app, path, &api_key, &options, job.settings.max_segment_secs);
+        if result.status != "success"
+            || result.text.is_empty()
+            || postprocess::is_hallucination(&result.text, &job.settings.hallucination_denylist)
+        {
+            continue;
+        }
+        report.recovered += 1;
+        history.update_entry(&entry.id, |entry| {
+            entry.text = result.text;
+            entry.status = result.status;
+            entry.round_trip_ms = Some(result.round_trip_ms);
+            entry.segments = result.segments;
+            entry.words = result.words;
+            entry.error = None;
+        });
+    }
+    if report.recovered > 0 {
+        tray::refresh_tray_menu(app);
+    }
+    Ok(report)
+}
+
+#[command]
+async fn retry_failed_sessions(app: tauri::AppHandle) -> Result<RetryReport, String> {
+    tauri::async_runtime::spawn_blocking(move || retry_failed_entries(&app))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[command]
+fn get_history_preview(id: String, max_chars: usize) -> Result<String, String> {
+    let history = HISTORY.get_or_init(History::new);
+    history
+        .get_entries()
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .map(|entry| history::preview(&entry.text, max_chars))
+        .ok_or_else(|| format!("No history entry with id {}", id))
+}
+
+fn parse_timestamp(value: &str) -> Result<chrono::DateTime<Utc>, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid timestamp '{}': {}", value, e))
+}
+
+#[command]
+fn delete_history_between(start: String, end: String) -> Result<usize, String> {
+    let start = parse_timestamp(&start)?;
+    let end = parse_timestamp(&end)?;
+    if start > end {
+        return Err("Start of range must not be after its end".to_string());
+    }
+    let history = HISTORY.get_or_init(History::new);
+    Ok(history.delete_between(start, end))
+}
+
+#[command]
+fn delete_history_older_than(days: u64) -> usize {
+    let history = HISTORY.get_or_init(History::new);
+    history.delete_older_than(days)
+}
+
+// Applies the retention setting immediately instead of waiting for the next session.
+#[command]
+fn prune_history_now(app: tauri::AppHandle) -> Result<usize, String> {
+    let days = settings::get_settings()?
+        .history_retention_days
+        .ok_or("No history retention period is set")?;
+    let removed = HISTORY.get_or_init(History::new).delete_older_than(days);
+    if removed > 0 {
+        tray::refresh_tray_menu(&app);
+    }
+    Ok(removed)
+}
+
+#[command]
+fn get_formatted_usage_stats() -> usage_stats::FormattedUsageStats {
+    let history = HISTORY.get_or_init(History::new);
+    usage_stats::UsageStats::from_entries(&history.get_entries()).formatted()
+}
+
+#[command]
+fn get_productivity_stats() -> usage_stats::ProductivityStats {
+    let history = HISTORY.get_or_init(History::new);
+    usage_stats::ProductivityStats::from_entries(&history.get_entries())
+}
+
+#[command]
+fn get_estimated_cost(price_per_minute: Option<f64>) -> Result<usage_stats::CostEstimate, String> {
+    let price_per_minute = match price_per_minute {
+        Some(price) => price,
+        None => settings::get_settings()?.price_per_minute,
+    };
+    let history = HISTORY.get_or_init(History::new);
+    Ok(usage_stats::estimate_month_cost(&history.get_entries(), price_per_minute))
+}
+
 #[tauri::command]
 fn get_audio_base64(path: String) -> Result<String, String> {
     let data = fs::read(path).map_err(|e| e.to_string())?;
@@ -218,7 +1238,8 @@ fn get_audio_base64(path: String) -> Result<String, String> {
 
 #[command]
 fn inject_text_manual(text: String) -> Result<(), String> {
-    textinjection::inject_text(&text).map_err(|e| e.to_string())
+    let settings = settings::get_settings()?;
+    textinjection::inject_text(&text, &settings.injection_fallback_chain).map_err(|e| e.to_string())
 }
 
 #[command]
@@ -233,12 +1254,14 @@ fn test_text_injection() -> Result<(), String> {
 
 #[command]
 fn check_for_updates() -> Result<update::UpdateInfo, String> {
-    update::check_for_updates().map_err(|e| e.to_string())
+    let settings = settings::get_settings()?;
+    update::check_for_updates(&settings.network).map_err(|e| e.to_string())
 }
 
 #[command]
 fn download_and_install_update(download_url: String, app: tauri::AppHandle) -> Result<update::UpdateResult, String> {
-    let result = update::download_and_install_update(download_url).map_err(|e| e.to_string())?;
+    let settings = settings::get_settings()?;
+    let result = update::download_and_install_update(download_url, &settings.network).map_err(|e| e.to_string())?;
     
     if result.success {
         std::thread::spawn(move || {
@@ -249,3 +1272,129 @@ fn download_and_install_update(download_url: String, app: tauri::AppHandle) -> R
     
     Ok(result)
 }
+
+#[command]
+fn quit_app(app: tauri::AppHandle) {
+    std::thread::spawn(move || shutdown(&app));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    // Answers one request per response, in order, and returns the server's base URL.
+    fn mock_server(responses: Vec<(u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                let mut chunked = false;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let header = line.trim().to_lowercase();
+                    if header.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                    chunked |= header == "transfer-encoding: chunked";
+                }
+                if chunked {
+                    loop {
+                        let mut size = String::new();
+                        reader.read_line(&mut size).unwrap();
+                        let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                        let mut chunk = vec![0u8; size + 2];
+                        reader.read_exact(&mut chunk).unwrap();
+                        if size == 0 {
+                            break;
+                        }
+                    }
+                } else {
+                    reader.read_exact(&mut vec![0u8; content_length]).unwrap();
+                }
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        base_url
+    }
+
+    fn write_silence(path: &std::path::Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..1600 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn server_errors_are_retried_until_the_transcription_succeeds() {
+        let unavailable = r#"{"error":{"message":"Service Unavailable"}}"#;
+        let base_url = mock_server(vec![(503, unavailable), (503, unavailable), (200, r#"{"text":" hello world "}"#)]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.wav");
+        write_silence(&path);
+        let options = transcription::TranscriptionOptions { base_url, ..Default::default() };
+
+        let mut waits = Vec::new();
+        let mut notices = Vec::new();
+        let result = retry_transcription(
+            || transcription::transcribe_audio(path.to_str().unwrap(), "", &options),
+            &options,
+            |delay| waits.push(delay),
+            |_, notice| notices.push(notice.attempt),
+        );
+
+        assert_eq!(result.status, "success");
+        assert_eq!(result.text, "hello world");
+        assert_eq!(waits, vec![TRANSIENT_RETRY_DELAY, TRANSIENT_RETRY_DELAY * 2]);
+        assert_eq!(notices, vec![1, 2]);
+    }
+
+    #[test]
+    fn capped_recording_is_submitted_without_a_key_event() {
+        let key = hotkey::trigger_key(30);
+        let mut monitor = hotkey::HotkeyMonitor::for_trigger(30, &settings::Settings::default());
+        // Presses right after the monitor is created count as bounce.
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(matches!(monitor.handle(&rdev::EventType::KeyPress(key)), Some(hotkey::HotkeyEvent::Pressed(_))));
+        *PENDING_JOB.lock().unwrap() = Some(ProcessingJob {
+            hold_time_ms: None,
+            action: hotkey::HotkeyAction::default(),
+            preview: false,
+            settings: settings::Settings::default(),
+            reply: None,
+        });
+
+        let (submitted_tx, submitted) = std::sync::mpsc::channel();
+        hand_off_key_session(move |job| {
+            let _ = submitted_tx.send(job.preview);
+        });
+        assert!(submitted.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert!(take_started_job().is_none());
+
+        // The release belongs to the recording that was already handed off.
+        assert!(monitor.handle(&rdev::EventType::KeyRelease(key)).is_none());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(matches!(monitor.handle(&rdev::EventType::KeyPress(key)), Some(hotkey::HotkeyEvent::Pressed(_))));
+    }
+}
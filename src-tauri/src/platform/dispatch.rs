@@ -0,0 +1,96 @@
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::{cancel_session, continuous, finish_session, is_processing, queue_session_task, start_session, take_started_job, transcription, ProcessingJob};
+use crate::hotkey::HotkeyEvent;
+
+pub type RestoreFocus = Box<dyn FnOnce() + Send>;
+
+// Turns the monitor's hotkey events into sessions. Shared by every platform's key monitor,
+// so continuous bindings, cancelling and the session queue behave the same everywhere.
+pub struct KeyDispatcher {
+    app_handle: AppHandle,
+    hold_start_time: Option<Instant>,
+    restore_focus: Option<RestoreFocus>,
+}
+
+impl KeyDispatcher {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self {
+            app_handle,
+            hold_start_time: None,
+            restore_focus: None,
+        }
+    }
+
+    // `remember_focus` runs when a recording starts, before the pill is shown, and returns
+    // how to give focus back to the window that had it.
+    pub fn dispatch(&mut self, event: HotkeyEvent, remember_focus: impl FnOnce() -> Option<RestoreFocus>) {
+        let app_handle = self.app_handle.clone();
+        match event {
+            HotkeyEvent::Pressed(action) if action.continuous => {
+                queue_session_task(move || {
+                    if continuous::is_running() {
+                        continuous::stop();
+                    } else if let Err(e) = continuous::start(app_handle, action) {
+                        eprintln!("Failed to start continuous dictation: {}", e);
+                    }
+                });
+            }
+            HotkeyEvent::Pressed(action) => {
+                self.hold_start_time = Some(Instant::now());
+                self.restore_focus = remember_focus();
+                if let Some(window) = app_handle.get_webview_window("main") {
+                    let _ = window.show();
+                }
+                let _ = app_handle.emit_to("main", "pill-state", "listening");
+                let _ = app_handle.emit_to("main", "start-recording", "");
+                queue_session_task(move || {
+                    if let Err(e) = start_session(ProcessingJob::new(action)) {
+                        eprintln!("Failed to start recording: {}", e);
+                    }
+                });
+            }
+            HotkeyEvent::Released(action) if action.continuous => {}
+            HotkeyEvent::Released(_) => {
+                let hold_time_ms = self.hold_start_time.take().map(|start| start.elapsed().as_millis() as u64);
+                let restore_focus = self.restore_focus.take();
+                queue_session_task(move || {
+                    // Nothing to stop if starting the recording failed.
+                    let Some(mut job) = take_started_job() else {
+                        let _ = app_handle.emit_to("main", "pill-state", "idle");
+                        if let Some(window) = app_handle.get_webview_window("main") {
+                            let _ = window.hide();
+                        }
+                        return;
+                    };
+                    job.hold_time_ms = hold_time_ms;
+                    finish_session(&app_handle, restore_focus, job);
+
+                    // Emit the hold time for potential frontend use
+                    if let Some(hold_time) = hold_time_ms {
+                        let _ = app_handle.emit_to("main", "hold-time", hold_time);
+                    }
+                });
+            }
+            HotkeyEvent::Cancelled => {
+                self.hold_start_time = None;
+                self.restore_focus = None;
+                queue_session_task(move || {
+                    cancel_session();
+                    let _ = app_handle.emit_to("main", "pill-state", "idle");
+                    if let Some(window) = app_handle.get_webview_window("main") {
+                        let _ = window.hide();
+                    }
+                });
+            }
+            HotkeyEvent::CancelProcessing if is_processing() => {
+                transcription::cancel_in_flight();
+                let _ = app_handle.emit_to("main", "pill-state", "idle");
+                if let Some(window) = app_handle.get_webview_window("main") {
+                    let _ = window.hide();
+                }
+            }
+            HotkeyEvent::CancelProcessing => {}
+        }
+    }
+}
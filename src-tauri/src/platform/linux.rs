@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use rdev::EventType;
+use tauri::AppHandle;
+use crate::settings;
+use crate::hid::{EventReader, HidTrigger, KeyState};
+use crate::hotkey::{self, HotkeyMonitor};
+use crate::platform::dispatch::KeyDispatcher;
+
+// Bumped to retire the running HID monitor when the trigger changes.
+static MONITOR_GENERATION: AtomicU64 = AtomicU64::new(0);
+// How often a monitor waiting for a key checks whether it has been replaced.
+const RETIRE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// Recording driven by a single key on an evdev device, e.g. a USB foot pedal.
+pub fn start_hid_monitor(app_handle: AppHandle, trigger: HidTrigger) {
+    let generation = MONITOR_GENERATION.load(Ordering::SeqCst);
+    thread::spawn(move || {
+        let mut reader = match EventReader::open_nonblocking(&trigger.device) {
+            Ok(reader) => reader,
+            Err(e) => {
+                eprintln!("Failed to start HID trigger: {}", e);
+                return;
+            }
+        };
+        let mut monitor = HotkeyMonitor::for_trigger(trigger.code, &settings::get_settings().unwrap_or_default());
+        let mut dispatcher = KeyDispatcher::new(app_handle.clone());
+        let key = hotkey::trigger_key(trigger.code);
+
+        hotkey::set_monitoring(true);
+        while MONITOR_GENERATION.load(Ordering::SeqCst) == generation {
+            let event = match reader.next_key_until(Instant::now() + RETIRE_CHECK_INTERVAL) {
+                Ok(Some((code, KeyState::Pressed))) if code == trigger.code => EventType::KeyPress(key),
+                Ok(Some((code, KeyState::Released))) if code == trigger.code => EventType::KeyRelease(key),
+                Ok(_) => continue,
+                Err(e) => {
+                    eprintln!("Error reading {}: {}", trigger.device, e);
+                    hotkey::set_monitoring(false);
+                    return;
+                }
+            };
+            // Focus isn't restored after injection on Linux.
+            if let Some(event) = monitor.handle(&event) {
+                dispatcher.dispatch(event, || None);
+            }
+        }
+        // The replacement monitor won't see the release of a recording started here.
+        crate::stop_key_session(&app_handle);
+    });
+}
+
+// Replaces the running HID monitor, if any, with one for `trigger`.
+pub fn restart_hid_monitor(app_handle: AppHandle, trigger: Option<HidTrigger>) {
+    MONITOR_GENERATION.fetch_add(1, Ordering::SeqCst);
+    match trigger {
+        Some(trigger) => start_hid_monitor(app_handle, trigger),
+        None => hotkey::set_monitoring(false),
+    }
+}
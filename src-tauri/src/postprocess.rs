@@ -0,0 +1,174 @@
+use std::cmp::Reverse;
+use serde::{Deserialize, Serialize};
+
+// A spoken phrase replaced by literal text when code mode is on, e.g. "open paren" -> "(".
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpokenCommand {
+    pub phrase: String,
+    pub replacement: String,
+}
+
+pub fn default_spoken_commands() -> Vec<SpokenCommand> {
+    [
+        ("open paren", "("),
+        ("close paren", ")"),
+        ("open bracket", "["),
+        ("close bracket", "]"),
+        ("open brace", "{"),
+        ("close brace", "}"),
+        ("semicolon", ";"),
+        ("colon", ":"),
+        ("comma", ","),
+        ("dot", "."),
+        ("equals", "="),
+        ("double quote", "\""),
+        ("single quote", "'"),
+        ("underscore", "_"),
+        ("new line", "\n"),
+        ("tab", "\t"),
+    ]
+    .iter()
+    .map(|(phrase, replacement)| SpokenCommand {
+        phrase: phrase.to_string(),
+        replacement: replacement.to_string(),
+    })
+    .collect()
+}
+
+enum Piece<'a> {
+    Word(&'a str),
+    // Symbols are joined to their neighbours without spaces.
+    Symbol(&'a str),
+}
+
+// Strips the punctuation Whisper attaches to words so "paren," still matches "paren".
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
+
+// Replaces spoken commands matched on whole words, longest phrase first, so "reparenting"
+// is left alone and "new line" wins over a shorter overlapping phrase.
+pub fn apply_spoken_commands(text: &str, commands: &[SpokenCommand]) -> String {
+    let mut phrases: Vec<(Vec<String>, &str)> = commands
+        .iter()
+        .map(|command| (command.phrase.split_whitespace().map(normalize).collect::<Vec<_>>(), command.replacement.as_str()))
+        .filter(|(words, _)| !words.is_empty())
+        .collect();
+    phrases.sort_by_key(|(words, _)| Reverse(words.len()));
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let normalized: Vec<String> = words.iter().map(|word| normalize(word)).collect();
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let matched = phrases.iter().find(|(phrase, _)| normalized[i..].starts_with(phrase));
+        match matched {
+            Some((phrase, replacement)) => {
+                if replacement.chars().any(char::is_alphanumeric) {
+                    pieces.push(Piece::Word(replacement));
+                } else {
+                    pieces.push(Piece::Symbol(replacement));
+                }
+                i += phrase.len();
+            }
+            None => {
+                pieces.push(Piece::Word(words[i]));
+                i += 1;
+            }
+        }
+    }
+
+    let mut output = String::new();
+    let mut previous_was_word = false;
+    for piece in pieces {
+        match piece {
+            Piece::Word(word) => {
+                if previous_was_word {
+                    output.push(' ');
+                }
+                output.push_str(word);
+                previous_was_word = true;
+            }
+            Piece::Symbol(symbol) => {
+                output.push_str(symbol);
+                previous_was_word = false;
+            }
+        }
+    }
+    output
+}
+
+// Phrases Whisper is known to produce on silent or noisy clips. Short ones such as
+// "thank you" are left out, since people dictate those on purpose.
+pub fn default_hallucination_denylist() -> Vec<String> {
+    [
+        "thanks for watching",
+        "thank you for watching",
+        "please subscribe",
+        "like and subscribe",
+        "subtitles by the amara.org community",
+    ]
+    .iter()
+    .map(|phrase| phrase.to_string())
+    .collect()
+}
+
+fn normalize_phrase(text: &str) -> String {
+    text.split_whitespace()
+        .map(normalize)
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// True when the whole transcript is one of the denylisted phrases, ignoring case,
+// punctuation and spacing. Transcripts that merely contain a phrase are kept.
+pub fn is_hallucination(text: &str, denylist: &[String]) -> bool {
+    let text = normalize_phrase(text);
+    !text.is_empty() && denylist.iter().any(|phrase| normalize_phrase(phrase) == text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str) -> String {
+        apply_spoken_commands(text, &default_spoken_commands())
+    }
+
+    #[test]
+    fn symbols_replace_their_phrases() {
+        assert_eq!(apply("foo open paren bar close paren semicolon"), "foo(bar);");
+        assert_eq!(apply("let x equals one"), "let x=one");
+    }
+
+    #[test]
+    fn punctuation_whisper_adds_is_ignored() {
+        assert_eq!(apply("Call open paren, close paren."), "Call()");
+    }
+
+    #[test]
+    fn multi_word_phrases_take_precedence() {
+        assert_eq!(apply("first new line second"), "first\nsecond");
+    }
+
+    #[test]
+    fn words_containing_a_phrase_are_left_alone() {
+        assert_eq!(apply("reparenting the node"), "reparenting the node");
+        assert_eq!(apply("the colony grew"), "the colony grew");
+    }
+
+    #[test]
+    fn wordy_replacements_keep_their_spacing() {
+        let commands = vec![SpokenCommand { phrase: "my email".to_string(), replacement: "me@example.com".to_string() }];
+        assert_eq!(apply_spoken_commands("send it to my email please", &commands), "send it to me@example.com please");
+    }
+
+    #[test]
+    fn everyday_phrases_are_not_hallucinations_by_default() {
+        let denylist = default_hallucination_denylist();
+        assert!(!is_hallucination("Thank you.", &denylist));
+        assert!(!is_hallucination("Bye", &denylist));
+        assert!(is_hallucination("Thanks for watching!", &denylist));
+    }
+}
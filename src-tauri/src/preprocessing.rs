@@ -0,0 +1,174 @@
+use std::f32::consts::PI;
+use std::path::Path;
+use hound::{WavReader, WavWriter};
+use serde::{Deserialize, Serialize};
+use crate::audio::sample_scale;
+
+// Peak level that normalization scales the recording up (or down) to.
+const NORMALIZE_PEAK: f32 = 0.95;
+
+// Every stage is off by default, which sends the recording exactly as captured.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessingConfig {
+    pub dc_removal: bool,
+    pub normalize: bool,
+    pub highpass_hz: Option<f32>,
+}
+
+impl PreprocessingConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.dc_removal || self.normalize || self.highpass_hz.is_some()
+    }
+}
+
+pub fn remove_dc(samples: &mut [f32]) {
+    if samples.is_empty() {
+        return;
+    }
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    for sample in samples.iter_mut() {
+        *sample -= mean;
+    }
+}
+
+pub fn normalize(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+    if peak <= f32::EPSILON {
+        return;
+    }
+    let gain = NORMALIZE_PEAK / peak;
+    for sample in samples.iter_mut() {
+        *sample *= gain;
+    }
+}
+
+// First-order RC high-pass filter.
+pub fn high_pass(samples: &mut [f32], cutoff_hz: f32, sample_rate: u32) {
+    if samples.is_empty() || cutoff_hz <= 0.0 {
+        return;
+    }
+    let rc = 1.0 / (2.0 * PI * cutoff_hz);
+    let dt = 1.0 / sample_rate as f32;
+    let alpha = rc / (rc + dt);
+    let mut prev_input = samples[0];
+    let mut prev_output = samples[0];
+    for sample in samples.iter_mut() {
+        let input = *sample;
+        let output = alpha * (prev_output + input - prev_input);
+        prev_input = input;
+        prev_output = output;
+        *sample = output;
+    }
+}
+
+pub fn apply(config: &PreprocessingConfig, samples: &mut [f32], sample_rate: u32) {
+    if config.dc_removal {
+        remove_dc(samples);
+    }
+    if let Some(cutoff_hz) = config.highpass_hz {
+        high_pass(samples, cutoff_hz, sample_rate);
+    }
+    if config.normalize {
+        normalize(samples);
+    }
+}
+
+// Rewrites a mono integer PCM recording in place with the configured chain applied.
+pub fn process_wav_file(path: &Path, config: &PreprocessingConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let scale = sample_scale(spec.bits_per_sample);
+    let mut samples = reader
+        .samples::<i32>()
+        .map(|s| s.map(|s| s as f32 / scale))
+        .collect::<Result<Vec<f32>, _>>()?;
+    drop(reader);
+
+    apply(config, &mut samples, spec.sample_rate);
+
+    // Write next to the original so a failure leaves the raw recording intact.
+    let tmp_path = path.with_extension("processed.wav");
+    let mut writer = WavWriter::create(&tmp_path, spec)?;
+    for sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * scale) as i32)?;
+    }
+    writer.finalize()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn sine(frequency: f32, amplitude: f32, offset: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE)
+            .map(|i| offset + amplitude * (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn mean(samples: &[f32]) -> f32 {
+        samples.iter().sum::<f32>() / samples.len() as f32
+    }
+
+    #[test]
+    fn dc_removal_centres_the_signal() {
+        let mut samples = sine(440.0, 0.2, 0.3);
+        remove_dc(&mut samples);
+        assert!(mean(&samples).abs() < 1e-4);
+        assert!((rms(&samples) - 0.2 / 2f32.sqrt()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn normalize_scales_to_the_target_peak() {
+        let mut samples = sine(440.0, 0.1, 0.0);
+        normalize(&mut samples);
+        let peak = samples.iter().fold(0.0f32, |peak, &s| peak.max(s.abs()));
+        assert!((peak - NORMALIZE_PEAK).abs() < 1e-4);
+    }
+
+    #[test]
+    fn normalize_leaves_silence_alone() {
+        let mut samples = vec![0.0; 160];
+        normalize(&mut samples);
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn high_pass_removes_rumble_and_keeps_speech() {
+        // Skips the filter settling at the start.
+        let settled = SAMPLE_RATE as usize / 10..;
+
+        let mut rumble = sine(20.0, 0.5, 0.0);
+        let before = rms(&rumble[settled.clone()]);
+        high_pass(&mut rumble, 100.0, SAMPLE_RATE);
+        assert!(rms(&rumble[settled.clone()]) < before * 0.3);
+
+        let mut speech = sine(1000.0, 0.5, 0.0);
+        let before = rms(&speech[settled.clone()]);
+        high_pass(&mut speech, 100.0, SAMPLE_RATE);
+        assert!(rms(&speech[settled]) > before * 0.95);
+    }
+
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut samples = vec![0.4; SAMPLE_RATE as usize];
+        high_pass(&mut samples, 80.0, SAMPLE_RATE);
+        assert!(samples[samples.len() - 1].abs() < 1e-3);
+    }
+
+    #[test]
+    fn disabled_chain_leaves_samples_untouched() {
+        let original = sine(440.0, 0.2, 0.1);
+        let mut samples = original.clone();
+        apply(&PreprocessingConfig::default(), &mut samples, SAMPLE_RATE);
+        assert_eq!(samples, original);
+    }
+}
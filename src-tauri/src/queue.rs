@@ -0,0 +1,120 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+
+pub type Task = Box<dyn FnOnce(Ticket) + Send>;
+
+// A small pool of long-lived workers consuming recordings in the order they were submitted.
+struct JobQueue {
+    state: Mutex<QueueState>,
+    available: Condvar,
+    turn_changed: Condvar,
+}
+
+struct QueueState {
+    pending: VecDeque<(u64, Task)>,
+    workers: usize,
+    max_workers: usize,
+    next_ticket: u64,
+    // Every ticket below this one has finished.
+    now_serving: u64,
+    finished: BTreeSet<u64>,
+}
+
+// Marks a job's place in submission order. Jobs may be transcribed in parallel, but
+// anything the user sees (injection, history) should happen after `wait_turn`.
+pub struct Ticket(u64);
+
+impl Ticket {
+    // Blocks until every job submitted before this one has finished.
+    pub fn wait_turn(&self) {
+        let queue = queue();
+        let mut state = queue.state.lock().unwrap();
+        while state.now_serving < self.0 {
+            state = queue.turn_changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let queue = queue();
+        let mut state = queue.state.lock().unwrap();
+        state.finished.insert(self.0);
+        while state.finished.remove(&state.now_serving) {
+            state.now_serving += 1;
+        }
+        queue.turn_changed.notify_all();
+    }
+}
+
+static QUEUE: OnceLock<JobQueue> = OnceLock::new();
+
+fn queue() -> &'static JobQueue {
+    QUEUE.get_or_init(|| JobQueue {
+        state: Mutex::new(QueueState {
+            pending: VecDeque::new(),
+            workers: 0,
+            max_workers: 1,
+            next_ticket: 0,
+            now_serving: 0,
+            finished: BTreeSet::new(),
+        }),
+        available: Condvar::new(),
+        turn_changed: Condvar::new(),
+    })
+}
+
+// Recordings waiting for a worker, not counting those being transcribed.
+pub fn pending_len() -> usize {
+    queue().state.lock().unwrap().pending.len()
+}
+
+// Queues `task` for the worker pool, growing or shrinking the pool to `max_concurrent`.
+// Fails without queueing when `max_depth` tasks are already waiting for a worker.
+pub fn submit(task: Task, max_concurrent: usize, max_depth: usize) -> Result<(), String> {
+    let queue = queue();
+    let mut state = queue.state.lock().unwrap();
+    if state.pending.len() >= max_depth.max(1) {
+        return Err(format!(
+            "{} recordings are already waiting to be transcribed, dropping this one",
+            state.pending.len()
+        ));
+    }
+    state.max_workers = max_concurrent.max(1);
+    let ticket = state.next_ticket;
+    state.next_ticket += 1;
+    state.pending.push_back((ticket, task));
+    while state.workers < state.max_workers {
+        state.workers += 1;
+        thread::spawn(|| worker(queue()));
+    }
+    // Wake everyone so a retiring worker can't swallow the only notification.
+    queue.available.notify_all();
+    Ok(())
+}
+
+fn worker(queue: &'static JobQueue) {
+    loop {
+        let (ticket, task) = {
+            let mut state = queue.state.lock().unwrap();
+            loop {
+                // Surplus workers retire once the limit has been lowered.
+                if state.workers > state.max_workers {
+                    state.workers -= 1;
+                    return;
+                }
+                if let Some(job) = state.pending.pop_front() {
+                    break job;
+                }
+                state = queue.available.wait(state).unwrap();
+            }
+        };
+        // The ticket is released when the task finishes or unwinds, so a failed job
+        // never holds up the ones behind it.
+        if panic::catch_unwind(AssertUnwindSafe(|| task(Ticket(ticket)))).is_err() {
+            eprintln!("Transcription job panicked");
+        }
+    }
+}
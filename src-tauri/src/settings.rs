@@ -1,37 +1,419 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use dirs::config_dir;
-use tauri::command;
+use std::sync::{Mutex, MutexGuard};
+use serde_json::Value;
+use tauri::{command, AppHandle, Emitter};
+use crate::storage;
+use crate::hotkey::{self, HotkeyAction, HotkeyBinding};
+use crate::network::NetworkConfig;
+use crate::transcription::{self, TranscriptionBackend};
+use crate::preprocessing::PreprocessingConfig;
+use crate::hid::HidTrigger;
+use crate::postprocess::{default_hallucination_denylist, default_spoken_commands, SpokenCommand};
 
-#[derive(Serialize, Deserialize, Default)]
+const SETTINGS_SCHEMA_VERSION: u32 = 2;
+pub const DEFAULT_HISTORY_MAX_ENTRIES: usize = 1000;
+pub const DEFAULT_MAX_SAVED_RECORDINGS: usize = 50;
+// Groq's whisper-large-v3-turbo list price of $0.04 per audio hour.
+pub const DEFAULT_PRICE_PER_MINUTE: f64 = 0.04 / 60.0;
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 1;
+pub const DEFAULT_MAX_QUEUE_DEPTH: usize = 5;
+pub const DEFAULT_VIZ_BAR_COUNT: usize = 10;
+pub const DEFAULT_VIZ_SENSITIVITY: f32 = 10.0;
+// Leaves room for rate-limit retries before the pill gives up on a job.
+pub const DEFAULT_PROCESSING_TIMEOUT_SECS: u64 = 90;
+pub const DEFAULT_API_PORT: u16 = 47615;
+pub const DEFAULT_PREROLL_MS: u64 = 150;
+pub const DEFAULT_MAX_RECORDING_SECS: u64 = 300;
+pub const DEFAULT_VOLUME_THRESHOLD: f32 = 0.01;
+pub const MAX_INPUT_GAIN_DB: f32 = 20.0;
+pub const DEFAULT_SILENCE_TIMEOUT_MS: u64 = 800;
+// Corrections of the same detection after which pinning a language is suggested.
+const LANGUAGE_PIN_SUGGESTION_COUNT: u32 = 3;
+pub const DEFAULT_SESSION_COOLDOWN_MS: u64 = 150;
+
+// Where audio is captured from. Loopback records what's playing through the speakers,
+// which is supported through WASAPI on Windows and PulseAudio/PipeWire monitor sources on Linux.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputSource {
+    #[default]
+    Microphone,
+    SystemLoopback,
+}
+
+// Which input channel is recorded on multi-channel devices.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureChannel {
+    // Only the first channel, for devices that duplicate a mono signal.
+    Mono,
+    Left,
+    Right,
+    #[default]
+    Average,
+    // Zero-based channel, for interfaces with more than two inputs.
+    Index(u16),
+}
+
+impl CaptureChannel {
+    // Folds one interleaved frame into a single sample.
+    pub fn select(self, frame: &[f32]) -> f32 {
+        let pick = |index: usize| frame.get(index).or(frame.last()).copied().unwrap_or(0.0);
+        match self {
+            CaptureChannel::Mono | CaptureChannel::Left => pick(0),
+            CaptureChannel::Right => pick(1),
+            CaptureChannel::Index(index) => pick(index as usize),
+            CaptureChannel::Average => {
+                if frame.is_empty() {
+                    0.0
+                } else {
+                    frame.iter().sum::<f32>() / frame.len() as f32
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectSubmit {
+    #[default]
+    Never,
+    Always,
+    // Only for apps whose name matches an entry in `submit_apps`.
+    PerApp,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    // Record while the hotkey is held.
+    #[default]
+    PushToTalk,
+    // Press once to start recording and again to stop.
+    Toggle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectionMethod {
+    // Paste through the clipboard, restoring its previous contents afterwards.
+    Clipboard,
+    // Simulate typing the text one character at a time.
+    Typing,
+}
+
+// Clipboard access off the main thread is unreliable on macOS, so it only types there.
+pub fn default_injection_chain() -> Vec<InjectionMethod> {
+    if cfg!(target_os = "windows") {
+        vec![InjectionMethod::Clipboard, InjectionMethod::Typing]
+    } else {
+        vec![InjectionMethod::Typing]
+    }
+}
+
+// A named bundle of transcription options, e.g. "formal email" or "code".
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub action: HotkeyAction,
+}
+
+// Encoding of the recording sent to Groq.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadFormat {
+    #[default]
+    Wav16,
+    // Keeps more detail in quiet recordings, especially after normalization, at 1.5x the size.
+    Wav24,
+}
+
+impl UploadFormat {
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            UploadFormat::Wav16 => 16,
+            UploadFormat::Wav24 => 24,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseDetail {
+    #[default]
+    Text,
+    // Timestamped segments in addition to the full text.
+    Segments,
+    // Segments plus the timing of every word, for timelines that follow playback.
+    Words,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
+    pub schema_version: u32,
     pub groq_api_key: Option<String>,
     pub save_history: bool,
     pub save_audio: bool,
+    // 0 keeps every entry.
+    pub history_max_entries: usize,
+    // Whether to press Return after injecting, e.g. to send a chat message.
+    pub inject_submit: InjectSubmit,
+    pub submit_apps: Vec<String>,
+    // Empty means a single push-to-talk binding on `hotkey`.
+    pub hotkey_bindings: Vec<HotkeyBinding>,
+    pub hotkey: String,
+    pub recording_mode: RecordingMode,
+    pub translate_to_english: bool,
+    pub response_detail: ResponseDetail,
+    // Used for the monthly cost estimate; Groq pricing changes, so it is configurable.
+    pub price_per_minute: f64,
+    pub preprocessing: PreprocessingConfig,
+    // How many recordings may be transcribed at once.
+    pub max_concurrent_jobs: usize,
+    // Recordings waiting for a free worker beyond this are rejected.
+    pub max_queue_depth: usize,
+    // Anonymous crash and error counts; off until the user opts in.
+    pub telemetry_enabled: bool,
+    pub telemetry_consent_asked: bool,
+    pub telemetry_endpoint: String,
+    // Skip injection when a password field seems to have focus; see secure_input_active.
+    pub block_secure_injection: bool,
+    // Linux only: an evdev device key used as push-to-talk.
+    pub hid_trigger: Option<HidTrigger>,
+    // Waveform shown in the pill; read when audio capture starts.
+    pub viz_bar_count: usize,
+    pub viz_sensitivity: f32,
+    // Scale the bars to recent peak levels instead of using viz_sensitivity.
+    pub viz_auto_gain: bool,
+    // Recordings longer than this are split at pauses and sent as several requests; 0 never splits.
+    pub max_segment_secs: u64,
+    // Collapse the pill if processing takes longer than this; 0 waits indefinitely.
+    pub processing_timeout_secs: u64,
+    // Read when audio capture starts.
+    pub capture_channel: CaptureChannel,
+    // Minimum gap between one recording being stopped and the next one starting.
+    pub session_cooldown_ms: u64,
+    // Substitutions applied by hotkeys bound with code mode.
+    pub spoken_commands: Vec<SpokenCommand>,
+    // Transcripts consisting only of one of these are treated as empty.
+    pub hallucination_denylist: Vec<String>,
+    // Show a system notification with the start of each injected transcript.
+    pub notify_on_success: bool,
+    // Pressing this while holding the hotkey discards the recording; empty disables it.
+    pub cancel_key: String,
+    // Proxy and extra headers for the Groq and update requests.
+    pub network: NetworkConfig,
+    // Tried in order until one succeeds.
+    pub injection_fallback_chain: Vec<InjectionMethod>,
+    // Local HTTP API for driving dictation from scripts; see api.rs.
+    pub api_enabled: bool,
+    pub api_port: u16,
+    pub api_token: String,
+    pub upload_format: UploadFormat,
+    // Audio from just before the hotkey was pressed to include in the recording; 0 disables it.
+    pub preroll_ms: u64,
+    // Keeps history and saved recordings here instead of the app directory.
+    pub data_dir_override: Option<PathBuf>,
+    // Every successful transcript is also appended here, e.g. a running notes file.
+    pub append_to_file: Option<PathBuf>,
+    // ISO-639-1 code sent when a hotkey doesn't set its own; None lets Groq detect it.
+    pub preferred_language: Option<String>,
+    // How often the user corrected each detected language, keyed by detected then corrected.
+    pub language_corrections: HashMap<String, HashMap<String, u32>>,
+    pub presets: Vec<Preset>,
+    // Name of the preset whose options apply to every session unless a hotkey overrides them.
+    pub active_preset: Option<String>,
+    // Microphone to record from, by name; None or a missing device uses the system default.
+    pub input_device_name: Option<String>,
+    // Capture rate to ask the device for, e.g. 16000 to skip resampling; None uses its default.
+    pub sample_rate: Option<u32>,
+    // Recordings stop growing after this long; 0 means no limit.
+    pub max_recording_secs: u64,
+    // RMS level below which audio counts as silence; raise it in rooms with background hum.
+    pub volume_threshold: f32,
+    pub input_source: InputSource,
+    // Applied to captured audio before anything else, on top of preprocessing's normalization.
+    pub input_gain_db: f32,
+    // Whether continuous dictation ends an utterance after `silence_timeout_ms` of quiet.
+    // When off, segments are only split at the recording length limit.
+    pub silence_autostop_enabled: bool,
+    pub silence_timeout_ms: u64,
+    // Only the newest recordings saved with save_audio are kept; 0 keeps all of them.
+    pub max_saved_recordings: usize,
+    // Used unless a hotkey binding or preset picks its own model.
+    pub groq_model: String,
+    // Sent as the prompt unless a binding or preset has its own.
+    pub transcription_prompt: Option<String>,
+    // Any server speaking the OpenAI audio API, e.g. OpenAI itself or a local whisper.cpp.
+    pub transcription_base_url: String,
+    pub transcription_backend: TranscriptionBackend,
+    // ggml model for the local backend, e.g. ggml-base.en.bin from the whisper.cpp releases.
+    pub local_model_path: Option<PathBuf>,
+    // Encrypt history text with a key kept in the OS keychain. Losing that keychain entry
+    // makes the encrypted entries unreadable.
+    pub encrypt_history: bool,
+    // Entries older than this many days are deleted; None keeps them until max_entries.
+    pub history_retention_days: Option<u64>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            groq_api_key: None,
+            save_history: true,
+            save_audio: true,
+            history_max_entries: DEFAULT_HISTORY_MAX_ENTRIES,
+            inject_submit: InjectSubmit::Never,
+            submit_apps: Vec::new(),
+            hotkey_bindings: Vec::new(),
+            hotkey: hotkey::default_hotkey().to_string(),
+            recording_mode: RecordingMode::PushToTalk,
+            translate_to_english: false,
+            response_detail: ResponseDetail::Text,
+            price_per_minute: DEFAULT_PRICE_PER_MINUTE,
+            preprocessing: PreprocessingConfig::default(),
+            max_concurrent_jobs: DEFAULT_MAX_CONCURRENT_JOBS,
+            max_queue_depth: DEFAULT_MAX_QUEUE_DEPTH,
+            telemetry_enabled: false,
+            telemetry_consent_asked: false,
+            telemetry_endpoint: String::new(),
+            block_secure_injection: true,
+            hid_trigger: None,
+            viz_bar_count: DEFAULT_VIZ_BAR_COUNT,
+            viz_sensitivity: DEFAULT_VIZ_SENSITIVITY,
+            viz_auto_gain: false,
+            max_segment_secs: 0,
+            processing_timeout_secs: DEFAULT_PROCESSING_TIMEOUT_SECS,
+            capture_channel: CaptureChannel::Average,
+            session_cooldown_ms: DEFAULT_SESSION_COOLDOWN_MS,
+            spoken_commands: default_spoken_commands(),
+            hallucination_denylist: default_hallucination_denylist(),
+            notify_on_success: false,
+            cancel_key: "Escape".to_string(),
+            network: NetworkConfig::default(),
+            injection_fallback_chain: default_injection_chain(),
+            api_enabled: false,
+            api_port: DEFAULT_API_PORT,
+            api_token: String::new(),
+            upload_format: UploadFormat::Wav16,
+            preroll_ms: DEFAULT_PREROLL_MS,
+            data_dir_override: None,
+            append_to_file: None,
+            preferred_language: None,
+            language_corrections: HashMap::new(),
+            presets: Vec::new(),
+            active_preset: None,
+            input_device_name: None,
+            sample_rate: None,
+            max_recording_secs: DEFAULT_MAX_RECORDING_SECS,
+            volume_threshold: DEFAULT_VOLUME_THRESHOLD,
+            input_source: InputSource::default(),
+            input_gain_db: 0.0,
+            silence_autostop_enabled: true,
+            silence_timeout_ms: DEFAULT_SILENCE_TIMEOUT_MS,
+            max_saved_recordings: DEFAULT_MAX_SAVED_RECORDINGS,
+            groq_model: transcription::DEFAULT_MODEL.to_string(),
+            transcription_prompt: None,
+            transcription_base_url: transcription::DEFAULT_BASE_URL.to_string(),
+            transcription_backend: TranscriptionBackend::default(),
+            local_model_path: None,
+            encrypt_history: false,
+            history_retention_days: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn active_preset(&self) -> Option<&Preset> {
+        let name = self.active_preset.as_deref()?;
+        self.presets.iter().find(|preset| preset.name == name)
+    }
+
+    // Local OpenAI-compatible servers usually don't check a key, so only Groq requires one.
+    pub fn api_key(&self) -> Result<String, String> {
+        match self.groq_api_key.clone().filter(|key| !key.is_empty()) {
+            Some(key) => Ok(key),
+            None if self.transcription_backend == TranscriptionBackend::Local => Ok(String::new()),
+            None if self.transcription_base_url != transcription::DEFAULT_BASE_URL => Ok(String::new()),
+            None => Err("No Groq API key set".to_string()),
+        }
+    }
+
+    // Groq and the local engine read 24-bit WAV, but other OpenAI-compatible servers may
+    // only handle 16-bit, which every one of them accepts.
+    pub fn effective_upload_format(&self) -> UploadFormat {
+        let accepts_any = self.transcription_backend == TranscriptionBackend::Local
+            || self.transcription_base_url == transcription::DEFAULT_BASE_URL;
+        if accepts_any || self.upload_format == UploadFormat::Wav16 {
+            return self.upload_format;
+        }
+        eprintln!("{:?} uploads may not be supported by {}, using 16-bit WAV", self.upload_format, self.transcription_base_url);
+        UploadFormat::Wav16
+    }
 }
 
 fn settings_path() -> PathBuf {
-    let mut path = config_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("vwisper");
-    fs::create_dir_all(&path).ok();
-    path.push("settings.json");
-    path
+    storage::app_file("settings.json")
 }
 
-fn load_settings() -> Settings {
-    let path = settings_path();
-    if let Ok(data) = fs::read_to_string(path) {
-        serde_json::from_str(&data).unwrap_or(Settings { save_history: true, save_audio: true, ..Default::default() })
-    } else {
-        Settings { save_history: true, save_audio: true, ..Default::default() }
+// Dropped from the default hallucination denylist in v2, since they are also things
+// people dictate on purpose.
+const RETIRED_DENYLIST_PHRASES: &[&str] = &["thank you", "you", "bye"];
+
+fn migrate_settings(value: &mut Value, from_version: u32) {
+    // v0 files predate schema_version but share the v1 layout, so the serde
+    // defaults already cover any missing field. Later layout changes go here.
+    if from_version < 2 {
+        if let Some(Value::Array(phrases)) = value.get_mut("hallucination_denylist") {
+            phrases.retain(|phrase| !phrase.as_str().is_some_and(|phrase| RETIRED_DENYLIST_PHRASES.contains(&phrase)));
+        }
     }
 }
 
+fn load_settings() -> Settings {
+    storage::load_versioned(&settings_path(), SETTINGS_SCHEMA_VERSION, migrate_settings)
+}
+
 fn save_settings_to_file(settings: &Settings) -> Result<(), String> {
     let path = settings_path();
     let data = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    fs::write(path, data).map_err(|e| e.to_string())
+    storage::write_atomic(&path, &data).map_err(|e| e.to_string())
+}
+
+// Lets every open window pick up the change without being reopened. The event reaches
+// every window, the pill included, so the secrets are left out of it.
+fn save_and_notify(app: &AppHandle, settings: &Settings) -> Result<(), String> {
+    save_settings_to_file(settings)?;
+    let mut payload = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    if let Some(fields) = payload.as_object_mut() {
+        fields.remove("groq_api_key");
+        fields.remove("api_token");
+        let has_api_key = settings.groq_api_key.as_ref().is_some_and(|key| !key.is_empty());
+        fields.insert("has_api_key".to_string(), Value::Bool(has_api_key));
+    }
+    let _ = app.emit("settings-updated", payload);
+    Ok(())
+}
+
+// Held for every load, modify and save of the settings file, so concurrent writers
+// can't overwrite each other's changes.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+fn write_lock() -> MutexGuard<'static, ()> {
+    WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn update_settings<F: FnOnce(&mut Settings)>(app: &AppHandle, update: F) -> Result<(), String> {
+    let _lock = write_lock();
+    let mut settings = load_settings();
+    update(&mut settings);
+    save_and_notify(app, &settings)
 }
 
 #[command]
@@ -40,7 +422,16 @@ pub fn get_settings() -> Result<Settings, String> {
 }
 
 #[command]
-pub fn save_settings(groq_api_key: String, save_history: Option<bool>, save_audio: Option<bool>) -> Result<(), String> {
+pub fn save_settings(
+    app: AppHandle,
+    groq_api_key: String,
+    save_history: Option<bool>,
+    save_audio: Option<bool>,
+    history_max_entries: Option<usize>,
+    silence_autostop_enabled: Option<bool>,
+    silence_timeout_ms: Option<u64>,
+) -> Result<(), String> {
+    let _lock = write_lock();
     let mut settings = load_settings();
     settings.groq_api_key = Some(groq_api_key);
     if let Some(val) = save_history {
@@ -49,19 +440,263 @@ pub fn save_settings(groq_api_key: String, save_history: Option<bool>, save_audi
     if let Some(val) = save_audio {
         settings.save_audio = val;
     }
-    save_settings_to_file(&settings)
+    if let Some(val) = history_max_entries {
+        settings.history_max_entries = val;
+    }
+    if let Some(val) = silence_autostop_enabled {
+        settings.silence_autostop_enabled = val;
+    }
+    if let Some(val) = silence_timeout_ms {
+        settings.silence_timeout_ms = val;
+    }
+    save_and_notify(&app, &settings)
 }
 
 #[command]
-pub fn reset_settings() -> Result<(), String> {
+pub fn reset_settings(app: AppHandle) -> Result<(), String> {
+    let _lock = write_lock();
     let mut settings = load_settings();
     settings.groq_api_key = None;
     settings.save_history = true;
     settings.save_audio = true;
-    save_settings_to_file(&settings)
+    settings.history_max_entries = DEFAULT_HISTORY_MAX_ENTRIES;
+    save_and_notify(&app, &settings)
+}
+
+impl InjectSubmit {
+    // `focused_app` is only queried in PerApp mode, since looking it up can be slow.
+    pub fn applies<F: FnOnce() -> Option<String>>(self, submit_apps: &[String], focused_app: F) -> bool {
+        match self {
+            InjectSubmit::Never => false,
+            InjectSubmit::Always => true,
+            InjectSubmit::PerApp => focused_app()
+                .map(|name| {
+                    let name = name.to_lowercase();
+                    submit_apps
+                        .iter()
+                        .any(|app| !app.is_empty() && name.contains(&app.to_lowercase()))
+                })
+                .unwrap_or(false),
+        }
+    }
 }
 
 #[command]
 pub fn get_settings_path() -> Result<String, String> {
     Ok(settings_path().to_string_lossy().to_string())
 }
+
+// Opens a file with its default application, or a directory in the file manager.
+fn open_path(path: &std::path::Path) -> Result<(), String> {
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(opener)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+#[command]
+pub fn open_config_file() -> Result<(), String> {
+    let path = settings_path();
+    // Give the user something to edit on a fresh install.
+    if !path.exists() {
+        let _lock = write_lock();
+        save_settings_to_file(&load_settings())?;
+    }
+    open_path(&path)
+}
+
+#[command]
+pub fn open_data_directory() -> Result<(), String> {
+    open_path(&storage::data_dir())
+}
+
+// Moves history and saved recordings to `path`, or back to the app directory when it is
+// empty, and remembers the choice. Refused when the target already holds history or saved
+// recordings, and nothing is moved unless everything can be.
+#[command]
+pub fn set_data_dir_override(app: AppHandle, path: Option<String>) -> Result<(), String> {
+    let data_dir_override = path
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    let target = data_dir_override.clone().unwrap_or_else(storage::app_dir);
+    storage::check_writable(&target).map_err(|e| format!("{} is not writable: {}", target.display(), e))?;
+
+    let current = storage::data_dir();
+    let same_dir = match (std::fs::canonicalize(&current), std::fs::canonicalize(&target)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => current == target,
+    };
+    if !same_dir {
+        let history = crate::HISTORY.get_or_init(crate::history::History::new);
+        history.flush();
+        // The in-memory history is written to the new location on its next save, so a
+        // history already there would be overwritten.
+        let moved = storage::move_all(&["history.json", "history.json.bak", "audio_out"], &current, &target)
+            .map_err(|e| format!("Can't use {}: {}", target.display(), e))?;
+        let audio_moved = moved.iter().any(|name| name == "audio_out");
+        storage::set_data_dir_override(data_dir_override.clone());
+        if audio_moved {
+            history.relocate_audio(&current.join("audio_out"), &target.join("audio_out"));
+        }
+    }
+    update_settings(&app, |settings| settings.data_dir_override = data_dir_override)
+}
+
+// Takes effect on the next key event, without restarting the key monitor.
+#[command]
+pub fn set_hotkey(app: AppHandle, key: String) -> Result<(), String> {
+    // Linux only listens to the HID trigger; there is no keyboard monitor to apply it to.
+    if cfg!(target_os = "linux") {
+        return Err("Keyboard hotkeys aren't supported on Linux; set up a HID trigger instead".to_string());
+    }
+    if hotkey::parse_key(&key).is_none() {
+        return Err(format!("Unknown key '{}'", key.trim()));
+    }
+    update_settings(&app, |settings| settings.hotkey = key.trim().to_string())?;
+    hotkey::reload_monitors();
+    Ok(())
+}
+
+#[command]
+pub fn set_recording_mode(app: AppHandle, mode: RecordingMode) -> Result<(), String> {
+    if cfg!(target_os = "linux") && load_settings().hid_trigger.is_none() {
+        return Err("On Linux the recording mode applies to the HID trigger; set one up first".to_string());
+    }
+    update_settings(&app, |settings| settings.recording_mode = mode)?;
+    hotkey::reload_monitors();
+    Ok(())
+}
+
+#[command]
+pub fn set_groq_model(app: AppHandle, model: String) -> Result<(), String> {
+    let is_groq = load_settings().transcription_base_url == transcription::DEFAULT_BASE_URL;
+    if is_groq && !transcription::MODELS.contains(&model.as_str()) {
+        return Err(format!("Unknown model '{}'", model));
+    }
+    update_settings(&app, |settings| settings.groq_model = model)
+}
+
+#[command]
+pub fn set_transcription_prompt(app: AppHandle, prompt: Option<String>) -> Result<(), String> {
+    let prompt = prompt.filter(|p| !p.trim().is_empty());
+    update_settings(&app, |settings| settings.transcription_prompt = prompt)
+}
+
+#[command]
+pub fn reset_transcription_prompt(app: AppHandle) -> Result<(), String> {
+    update_settings(&app, |settings| settings.transcription_prompt = None)
+}
+
+#[command]
+pub fn set_preferred_language(app: AppHandle, language: Option<String>) -> Result<(), String> {
+    let language = language.map(|l| l.trim().to_lowercase()).filter(|l| !l.is_empty());
+    update_settings(&app, |settings| settings.preferred_language = language)
+}
+
+#[command]
+pub fn set_input_gain(app: AppHandle, gain_db: f32) -> Result<f32, String> {
+    if !gain_db.is_finite() {
+        return Err("Gain must be a number".to_string());
+    }
+    let gain_db = gain_db.clamp(-MAX_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB);
+    update_settings(&app, |settings| settings.input_gain_db = gain_db)?;
+    crate::audio::set_input_gain(gain_db);
+    Ok(gain_db)
+}
+
+// Records that a transcript detected as `detected` was really `corrected`. Returns true once
+// the same correction keeps recurring, so the UI can offer to pin `corrected`.
+#[command]
+pub fn record_language_correction(app: AppHandle, detected: String, corrected: String) -> Result<bool, String> {
+    let mut count = 0;
+    update_settings(&app, |settings| {
+        let entry = settings
+            .language_corrections
+            .entry(detected.to_lowercase())
+            .or_default()
+            .entry(corrected.to_lowercase())
+            .or_default();
+        *entry += 1;
+        count = *entry;
+    })?;
+    Ok(count >= LANGUAGE_PIN_SUGGESTION_COUNT)
+}
+
+#[command]
+pub fn list_presets() -> Vec<Preset> {
+    load_settings().presets
+}
+
+// Adds the preset, or replaces the one with the same name.
+#[command]
+pub fn save_preset(app: AppHandle, name: String, action: HotkeyAction) -> Result<(), String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("A preset needs a name".to_string());
+    }
+    update_settings(&app, |settings| {
+        match settings.presets.iter_mut().find(|preset| preset.name == name) {
+            Some(preset) => preset.action = action,
+            None => settings.presets.push(Preset { name, action }),
+        }
+    })
+}
+
+// `None` goes back to the plain settings.
+#[command]
+pub fn apply_preset(app: AppHandle, name: Option<String>) -> Result<(), String> {
+    if let Some(name) = &name {
+        if !load_settings().presets.iter().any(|preset| &preset.name == name) {
+            return Err(format!("No preset named '{}'", name));
+        }
+    }
+    update_settings(&app, |settings| settings.active_preset = name)
+}
+
+#[command]
+pub fn delete_preset(app: AppHandle, name: String) -> Result<(), String> {
+    update_settings(&app, |settings| {
+        settings.presets.retain(|preset| preset.name != name);
+        if settings.active_preset.as_deref() == Some(name.as_str()) {
+            settings.active_preset = None;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_settings_load_into_the_current_struct() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "schema_version": 1,
+                "groq_api_key": "gsk_test",
+                "save_history": false,
+                "hallucination_denylist": ["thank you", "thanks for watching", "my own phrase", "bye"]
+            }"#,
+        )
+        .unwrap();
+
+        let settings: Settings = storage::load_versioned(&path, SETTINGS_SCHEMA_VERSION, migrate_settings);
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(settings.groq_api_key.as_deref(), Some("gsk_test"));
+        assert!(!settings.save_history);
+        assert_eq!(settings.hallucination_denylist, vec!["thanks for watching", "my own phrase"]);
+        assert_eq!(settings.recording_mode, RecordingMode::PushToTalk);
+        assert_eq!(settings.max_recording_secs, DEFAULT_MAX_RECORDING_SECS);
+    }
+}
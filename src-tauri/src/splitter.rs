@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+use hound::{WavReader, WavWriter};
+
+// Silence is measured over frames this long.
+const FRAME_MS: usize = 20;
+
+pub struct Part {
+    pub path: PathBuf,
+    // Offset of the part within the original recording.
+    pub start_secs: f64,
+}
+
+// Unscaled, which is fine since it is only compared against other frames of the same file.
+fn frame_rms(frame: &[i32]) -> f64 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = frame.iter().map(|&s| (s as f64).powi(2)).sum();
+    (sum / frame.len() as f64).sqrt()
+}
+
+// Picks where to end a part that starts at `start`, at the quietest frame in the second
+// half of the allowed length. If nobody pauses for that whole stretch the part is still
+// cut at its quietest point, since staying under the length limit takes priority.
+fn find_split(samples: &[i32], start: usize, max_len: usize, frame_len: usize) -> usize {
+    let search_start = start + max_len / 2;
+    let search_end = start + max_len;
+    let mut best = search_end;
+    let mut best_rms = f64::MAX;
+    let mut pos = search_start;
+    while pos + frame_len <= search_end {
+        let rms = frame_rms(&samples[pos..pos + frame_len]);
+        // Later frames win ties so parts come out as long as allowed.
+        if rms <= best_rms {
+            best_rms = rms;
+            best = pos + frame_len / 2;
+        }
+        pos += frame_len;
+    }
+    best
+}
+
+// Splits a mono integer PCM recording into parts no longer than `max_segment_secs`, cutting at
+// pauses where possible. Returns None when the recording is short enough to send whole.
+pub fn split_wav_file(path: &Path, max_segment_secs: u64) -> Result<Option<Vec<Part>>, Box<dyn std::error::Error>> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    let max_len = (spec.sample_rate as u64 * max_segment_secs) as usize;
+    if max_len == 0 || reader.len() as usize <= max_len {
+        return Ok(None);
+    }
+    let samples = reader.samples::<i32>().collect::<Result<Vec<i32>, _>>()?;
+    drop(reader);
+
+    let frame_len = (spec.sample_rate as usize * FRAME_MS / 1000).max(1);
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while start < samples.len() {
+        let end = if samples.len() - start <= max_len {
+            samples.len()
+        } else {
+            find_split(&samples, start, max_len, frame_len)
+        };
+        let part_path = path.with_extension(format!("part{}.wav", parts.len()));
+        let mut writer = WavWriter::create(&part_path, spec)?;
+        for &sample in &samples[start..end] {
+            writer.write_sample(sample)?;
+        }
+        writer.finalize()?;
+        parts.push(Part {
+            path: part_path,
+            start_secs: start as f64 / spec.sample_rate as f64,
+        });
+        start = end;
+    }
+    Ok(Some(parts))
+}
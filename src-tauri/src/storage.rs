@@ -0,0 +1,370 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use dirs::config_dir;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub const APP_DIR_NAME: &str = "vwisper";
+
+// Older builds stored some files under a capitalized directory name.
+const LEGACY_APP_DIR_NAMES: &[&str] = &["VWisper"];
+
+// Where history and saved recordings go instead of the app directory. Settings always
+// stay in the app directory, since that is where the override itself is read from.
+static DATA_DIR_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+fn base_dir() -> PathBuf {
+    config_dir().unwrap_or_else(|| PathBuf::from("."))
+}
+
+pub fn app_dir() -> PathBuf {
+    let path = base_dir().join(APP_DIR_NAME);
+    fs::create_dir_all(&path).ok();
+    path
+}
+
+pub fn app_file(name: &str) -> PathBuf {
+    app_dir().join(name)
+}
+
+pub fn app_subdir(name: &str) -> PathBuf {
+    let path = app_dir().join(name);
+    fs::create_dir_all(&path).ok();
+    path
+}
+
+pub fn set_data_dir_override(path: Option<PathBuf>) {
+    *DATA_DIR_OVERRIDE.write().unwrap() = path;
+}
+
+pub fn data_dir() -> PathBuf {
+    match &*DATA_DIR_OVERRIDE.read().unwrap() {
+        Some(path) => {
+            fs::create_dir_all(path).ok();
+            path.clone()
+        }
+        None => app_dir(),
+    }
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    data_dir().join(name)
+}
+
+pub fn data_subdir(name: &str) -> PathBuf {
+    let path = data_dir().join(name);
+    fs::create_dir_all(&path).ok();
+    path
+}
+
+// Creates `path` if needed and checks a file can be written there.
+pub fn check_writable(path: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(path)?;
+    let probe = path.join(".vwisper_write_test");
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+// Moves a file or directory, copying when a plain rename isn't possible, e.g. across drives.
+pub fn move_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}
+
+// Moves whichever of `names` exist from `from` into `to`, all or nothing: if one fails,
+// those already moved are moved back. Refuses when any of them is already at `to`, since
+// merging is not supported. Returns the names that were moved.
+pub fn move_all(names: &[&str], from: &Path, to: &Path) -> Result<Vec<String>, String> {
+    move_all_with(names, from, to, move_path)
+}
+
+fn move_all_with(
+    names: &[&str],
+    from: &Path,
+    to: &Path,
+    mover: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<Vec<String>, String> {
+    if let Some(existing) = names.iter().map(|name| to.join(name)).find(|path| path.exists()) {
+        return Err(format!("{} already exists", existing.display()));
+    }
+    let mut moved = Vec::new();
+    for name in names {
+        let source = from.join(name);
+        if !source.exists() {
+            continue;
+        }
+        if let Err(e) = mover(&source, &to.join(name)) {
+            for done in moved.iter().rev() {
+                if let Err(e) = mover(&to.join(done), &from.join(done)) {
+                    eprintln!("Failed to move {} back: {}", to.join(done).display(), e);
+                }
+            }
+            return Err(format!("Failed to move {}: {}", source.display(), e));
+        }
+        moved.push(name.to_string());
+    }
+    Ok(moved)
+}
+
+pub fn migrate_legacy_dirs() {
+    let canonical = app_dir();
+    for legacy_name in LEGACY_APP_DIR_NAMES {
+        let legacy = base_dir().join(legacy_name);
+        if !legacy.is_dir() {
+            continue;
+        }
+        // On case-insensitive filesystems both names resolve to the same directory.
+        if let (Ok(a), Ok(b)) = (fs::canonicalize(&legacy), fs::canonicalize(&canonical)) {
+            if a == b {
+                continue;
+            }
+        }
+        let entries = match fs::read_dir(&legacy) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Failed to read legacy data directory {}: {}", legacy.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let target = canonical.join(entry.file_name());
+            if target.exists() {
+                eprintln!("Skipping migration of {}: already exists in {}", entry.path().display(), canonical.display());
+                continue;
+            }
+            if let Err(e) = fs::rename(entry.path(), &target) {
+                eprintln!("Failed to migrate {}: {}", entry.path().display(), e);
+            }
+        }
+        // Only removes the directory if everything was moved out of it.
+        let _ = fs::remove_dir(&legacy);
+    }
+}
+
+// Loads a JSON state file, upgrading older layouts with `migrate` before deserializing.
+// `migrate` receives the document and the version it was written with, and must bring it
+// up to `current_version`. Unreadable files are set aside instead of being overwritten,
+// and the last good copy kept by `write_atomic` is used in their place.
+pub fn load_versioned<T: DeserializeOwned + Default>(
+    path: &Path,
+    current_version: u32,
+    migrate: fn(&mut Value, u32),
+) -> T {
+    if let Some(parsed) = try_load(path, current_version, migrate) {
+        return parsed;
+    }
+    let backup = sibling(path, ".bak");
+    if let Some(parsed) = try_load(&backup, current_version, migrate) {
+        eprintln!("Recovered {} from {}", path.display(), backup.display());
+        return parsed;
+    }
+    T::default()
+}
+
+fn try_load<T: DeserializeOwned>(
+    path: &Path,
+    current_version: u32,
+    migrate: fn(&mut Value, u32),
+) -> Option<T> {
+    let data = fs::read_to_string(path).ok()?;
+    let mut value: Value = match serde_json::from_str(&data) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {}", path.display(), e);
+            set_aside(path);
+            return None;
+        }
+    };
+
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+    if version > current_version {
+        eprintln!(
+            "{} was written by a newer version (schema {}, expected {})",
+            path.display(),
+            version,
+            current_version
+        );
+    } else if version < current_version {
+        migrate(&mut value, version);
+        if let Some(object) = value.as_object_mut() {
+            object.insert("schema_version".to_string(), Value::from(current_version));
+        }
+    }
+
+    match serde_json::from_value(value) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+            set_aside(path);
+            None
+        }
+    }
+}
+
+// Writes to a temp file next to `path` and renames it into place, so a crash mid-write
+// never leaves a truncated file behind. The previous contents are kept as `<file>.bak`.
+pub fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = sibling(path, ".tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+    }
+    if path.exists() {
+        if let Err(e) = fs::copy(path, sibling(path, ".bak")) {
+            eprintln!("Failed to back up {}: {}", path.display(), e);
+        }
+    }
+    fs::rename(&tmp, path)
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn set_aside(path: &Path) {
+    if let Err(e) = fs::rename(path, sibling(path, ".corrupt")) {
+        eprintln!("Failed to set aside {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize)]
+    #[serde(default)]
+    struct Sample {
+        schema_version: u32,
+        name: String,
+        count: u32,
+    }
+
+    // v1 renamed `title` to `name`.
+    fn migrate_sample(value: &mut Value, from_version: u32) {
+        if from_version < 1 {
+            if let Some(object) = value.as_object_mut() {
+                if let Some(title) = object.remove("title") {
+                    object.insert("name".to_string(), title);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unversioned_file_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.json");
+        fs::write(&path, r#"{ "title": "kept", "count": 3 }"#).unwrap();
+
+        let sample: Sample = load_versioned(&path, 1, migrate_sample);
+        assert_eq!(sample.schema_version, 1);
+        assert_eq!(sample.name, "kept");
+        assert_eq!(sample.count, 3);
+    }
+
+    #[test]
+    fn current_file_is_loaded_as_is() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.json");
+        fs::write(&path, r#"{ "schema_version": 1, "name": "kept", "title": "ignored" }"#).unwrap();
+
+        let sample: Sample = load_versioned(&path, 1, migrate_sample);
+        assert_eq!(sample.name, "kept");
+        assert_eq!(sample.count, 0);
+    }
+
+    #[test]
+    fn missing_file_gives_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let sample: Sample = load_versioned(&dir.path().join("missing.json"), 1, migrate_sample);
+        assert_eq!(sample.name, "");
+    }
+
+    #[test]
+    fn interrupted_write_leaves_the_previous_file_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.json");
+        write_atomic(&path, r#"{ "schema_version": 1, "name": "good" }"#).unwrap();
+        // A crash mid-write only ever leaves a partial temp file behind.
+        fs::write(sibling(&path, ".tmp"), r#"{ "schema_version": 1, "na"#).unwrap();
+
+        let sample: Sample = load_versioned(&path, 1, migrate_sample);
+        assert_eq!(sample.name, "good");
+    }
+
+    #[test]
+    fn truncated_file_is_recovered_from_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sample.json");
+        write_atomic(&path, r#"{ "schema_version": 1, "name": "previous" }"#).unwrap();
+        write_atomic(&path, r#"{ "schema_version": 1, "name": "latest" }"#).unwrap();
+        fs::write(&path, r#"{ "schema_version": 1, "na"#).unwrap();
+
+        let sample: Sample = load_versioned(&path, 1, migrate_sample);
+        assert_eq!(sample.name, "previous");
+        assert!(sibling(&path, ".corrupt").exists());
+    }
+
+    #[test]
+    fn move_all_refuses_a_target_that_already_has_the_files() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        fs::write(from.path().join("history.json"), "mine").unwrap();
+        fs::write(to.path().join("history.json"), "theirs").unwrap();
+
+        assert!(move_all(&["history.json"], from.path(), to.path()).is_err());
+        assert_eq!(fs::read_to_string(from.path().join("history.json")).unwrap(), "mine");
+        assert_eq!(fs::read_to_string(to.path().join("history.json")).unwrap(), "theirs");
+    }
+
+    #[test]
+    fn failed_move_puts_back_what_was_already_moved() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        fs::write(from.path().join("history.json"), "entries").unwrap();
+        fs::create_dir(from.path().join("audio_out")).unwrap();
+        let failing = |source: &Path, target: &Path| {
+            if source.ends_with("audio_out") {
+                return Err(std::io::Error::other("disk full"));
+            }
+            move_path(source, target)
+        };
+
+        assert!(move_all_with(&["history.json", "audio_out"], from.path(), to.path(), failing).is_err());
+        assert_eq!(fs::read_to_string(from.path().join("history.json")).unwrap(), "entries");
+        assert!(from.path().join("audio_out").is_dir());
+        assert!(!to.path().join("history.json").exists());
+    }
+
+    #[test]
+    fn missing_files_are_skipped() {
+        let from = tempfile::tempdir().unwrap();
+        let to = tempfile::tempdir().unwrap();
+        fs::write(from.path().join("history.json"), "entries").unwrap();
+
+        let moved = move_all(&["history.json", "audio_out"], from.path(), to.path()).unwrap();
+        assert_eq!(moved, vec!["history.json"]);
+        assert!(to.path().join("history.json").exists());
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use serde_json::json;
+use tauri::{command, AppHandle};
+use crate::settings;
+
+// Reports only ever carry counts, categories and source locations: never transcript
+// text, panic messages (which can embed user data) or the API key.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+static ENDPOINT: OnceLock<String> = OnceLock::new();
+static ERROR_COUNTS: Mutex<Option<HashMap<&'static str, u64>>> = Mutex::new(None);
+
+#[derive(Debug, Serialize)]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    // False until the user has answered the consent prompt once.
+    pub consent_asked: bool,
+    pub endpoint: String,
+}
+
+// Installs the panic hook when the user has opted in. Does nothing otherwise, and
+// nothing is recorded or sent until the next launch after opting in.
+pub fn init() {
+    let settings = settings::get_settings().unwrap_or_default();
+    if !settings.telemetry_enabled || settings.telemetry_endpoint.trim().is_empty() {
+        return;
+    }
+    if ENDPOINT.set(settings.telemetry_endpoint.trim().to_string()).is_err() {
+        return;
+    }
+    *ERROR_COUNTS.lock().unwrap() = Some(HashMap::new());
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        // The blocking client refuses to run on an async runtime thread, so send from a fresh one.
+        let _ = std::thread::spawn(move || send(json!({ "kind": "panic", "location": location }))).join();
+        default_hook(info);
+    }));
+}
+
+fn enabled() -> bool {
+    ENDPOINT.get().is_some()
+}
+
+// Buckets an error message into a coarse category so the text itself is never sent.
+pub fn error_category(error: &str) -> &'static str {
+    let error = error.to_lowercase();
+    if error.contains("429") {
+        "rate_limited"
+    } else if error.contains("401") || error.contains("403") {
+        "unauthorized"
+    } else if error.contains("groq api error") {
+        "api_error"
+    } else if error.contains("timed out") || error.contains("connect") || error.contains("dns") {
+        "network"
+    } else {
+        "other"
+    }
+}
+
+pub fn record_error(category: &'static str) {
+    if let Some(counts) = ERROR_COUNTS.lock().unwrap().as_mut() {
+        *counts.entry(category).or_insert(0) += 1;
+    }
+}
+
+// Sends the accumulated error counts, if any, and resets them.
+pub fn flush() {
+    if !enabled() {
+        return;
+    }
+    let counts = match ERROR_COUNTS.lock().unwrap().as_mut() {
+        Some(counts) if !counts.is_empty() => std::mem::take(counts),
+        _ => return,
+    };
+    send(json!({ "kind": "errors", "counts": counts }));
+}
+
+fn send(mut report: serde_json::Value) {
+    let endpoint = match ENDPOINT.get() {
+        Some(endpoint) => endpoint,
+        None => return,
+    };
+    report["app_version"] = json!(env!("CARGO_PKG_VERSION"));
+    report["os"] = json!(std::env::consts::OS);
+    let client = match Client::builder().timeout(SEND_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(_) => return,
+    };
+    let request = client
+        .post(endpoint)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(report.to_string());
+    if let Err(e) = request.send() {
+        eprintln!("Failed to send telemetry: {}", e);
+    }
+}
+
+#[command]
+pub fn get_telemetry_status() -> Result<TelemetryStatus, String> {
+    let settings = settings::get_settings()?;
+    Ok(TelemetryStatus {
+        enabled: settings.telemetry_enabled,
+        consent_asked: settings.telemetry_consent_asked,
+        endpoint: settings.telemetry_endpoint,
+    })
+}
+
+#[command]
+pub fn set_telemetry_consent(app: AppHandle, enabled: bool) -> Result<(), String> {
+    settings::update_settings(&app, |settings| {
+        settings.telemetry_enabled = enabled;
+        settings.telemetry_consent_asked = true;
+    })
+}
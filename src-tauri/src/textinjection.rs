@@ -4,10 +4,17 @@ use once_cell::sync::Lazy;
 use log::{info, warn};
 use std::thread;
 use std::time::Duration;
+use crate::settings::InjectionMethod;
 
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 use clipboard::{ClipboardProvider, ClipboardContext};
 
+// Pause between typed characters.
+#[cfg(target_os = "windows")]
+const TYPING_DELAY: Duration = Duration::from_millis(10);
+#[cfg(target_os = "macos")]
+const TYPING_DELAY: Duration = Duration::from_millis(6);
+
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 static TEXT_INJECTOR: Lazy<Arc<Mutex<Option<Enigo>>>> = Lazy::new(|| {
     Arc::new(Mutex::new(None))
@@ -50,10 +57,11 @@ pub fn is_text_injector_initialized() -> bool {
 pub fn test_text_injection() -> Result<(), Box<dyn std::error::Error>> {
     let test_text = "VWisper test";
     info!("Testing text injection with: '{}'", test_text);
-    inject_text(test_text)
+    let settings = crate::settings::get_settings()?;
+    inject_text(test_text, &settings.injection_fallback_chain)
 }
 
-pub fn inject_text(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn inject_text(text: &str, methods: &[InjectionMethod]) -> Result<(), Box<dyn std::error::Error>> {
     if !is_text_injector_initialized() {
         return Err("Text injector not initialized. Please restart the application.".into());
     }
@@ -62,25 +70,39 @@ pub fn inject_text(text: &str) -> Result<(), Box<dyn std::error::Error>> {
         return Err("Cannot inject empty text".into());
     }
 
+    if methods.is_empty() {
+        return Err("No injection methods configured".into());
+    }
+
     // Add a small delay to ensure the target window is fully focused
     thread::sleep(Duration::from_millis(200));
 
-    #[cfg(target_os = "windows")]
-    {
-        info!("Injecting text on Windows: '{}'", text);
-        return inject_text_via_clipboard(text);
+    let mut failures = Vec::new();
+    for &method in methods {
+        info!("Injecting text on {} via {:?}: '{}'", std::env::consts::OS, method, text);
+        let result = match method {
+            InjectionMethod::Clipboard => inject_text_via_clipboard(text),
+            InjectionMethod::Typing => inject_text_by_typing(text),
+        };
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                warn!("{:?} injection failed: {}", method, e);
+                failures.push(format!("{:?}: {}", method, e));
+            }
+        }
     }
+    Err(format!("All injection methods failed ({})", failures.join("; ")).into())
+}
 
-    #[cfg(target_os = "macos")]
+fn inject_text_by_typing(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
     {
-        info!("Injecting text on macOS via direct typing (clipboard-free): '{}'", text);
-        return inject_text_char_by_character(text);
+        inject_text_char_by_character(text)
     }
-    
+
     #[cfg(target_os = "linux")]
     {
-        info!("Injecting text on {}: '{}'", std::env::consts::OS, text);
-        
         let mut injector_guard = TEXT_INJECTOR.lock().unwrap();
         
         if let Some(injector) = injector_guard.as_mut() {
@@ -104,6 +126,114 @@ pub fn inject_text(text: &str) -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+// Sends a single Return keypress, used to submit after the transcript was injected.
+pub fn press_return() -> Result<(), Box<dyn std::error::Error>> {
+    use enigo::{Key, Direction};
+
+    let mut injector_guard = TEXT_INJECTOR.lock().unwrap();
+    if let Some(injector) = injector_guard.as_mut() {
+        thread::sleep(Duration::from_millis(50));
+        injector.key(Key::Return, Direction::Click)
+            .map_err(|e| format!("Failed to press Return: {}", e))?;
+        Ok(())
+    } else {
+        Err("Text injector not initialized".into())
+    }
+}
+
+// Best-effort name of the application (macOS) or window title (Windows) that has focus.
+pub fn focused_app_name() -> Option<String> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW};
+
+        let mut buffer = [0u16; 512];
+        let len = unsafe { GetWindowTextW(GetForegroundWindow(), &mut buffer) };
+        if len <= 0 {
+            return None;
+        }
+        return Some(String::from_utf16_lossy(&buffer[..len as usize]));
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let output = std::process::Command::new("osascript")
+            .arg("-e")
+            .arg("tell application \"System Events\" to get name of first application process whose frontmost is true")
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        return if name.is_empty() { None } else { Some(name) };
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        None
+    }
+}
+
+// Whether one of our own windows (the pill or the dashboard) currently has focus.
+// Only checked on Windows: the macOS monitor doesn't capture the previous app yet, so
+// focus is never handed back there and this would block every injection.
+pub fn own_window_focused() -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+        let mut pid = 0u32;
+        unsafe {
+            GetWindowThreadProcessId(GetForegroundWindow(), Some(&mut pid));
+        }
+        return pid == std::process::id();
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        false
+    }
+}
+
+// Best-effort check for a password field or other secure input context under focus.
+// Windows only recognizes native edit controls with ES_PASSWORD, so password fields
+// drawn by browsers or custom toolkits are missed. macOS reports the system-wide
+// secure input mode, which those fields enable, but so can any app that holds it.
+pub fn secure_input_active() -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::UI::WindowsAndMessaging::{
+            GetForegroundWindow, GetGUIThreadInfo, GetWindowLongW, GetWindowThreadProcessId,
+            GUITHREADINFO, GWL_STYLE,
+        };
+        const ES_PASSWORD: i32 = 0x0020;
+
+        let mut info = GUITHREADINFO {
+            cbSize: std::mem::size_of::<GUITHREADINFO>() as u32,
+            ..Default::default()
+        };
+        return unsafe {
+            let thread_id = GetWindowThreadProcessId(GetForegroundWindow(), None);
+            if GetGUIThreadInfo(thread_id, &mut info).is_err() || info.hwndFocus.0 == 0 {
+                return false;
+            }
+            GetWindowLongW(info.hwndFocus, GWL_STYLE) & ES_PASSWORD != 0
+        };
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        #[link(name = "Carbon", kind = "framework")]
+        extern "C" {
+            fn IsSecureEventInputEnabled() -> u8;
+        }
+        return unsafe { IsSecureEventInputEnabled() != 0 };
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        false
+    }
+}
+
 #[cfg(target_os = "windows")]
 fn inject_text_via_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
     use enigo::{Key, Direction};
@@ -130,7 +260,6 @@ fn inject_text_via_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error
         
         thread::sleep(focus_delay);
         
-        // Try the clipboard approach first
         let clipboard_result = {
             // Use Ctrl+V on Windows with enhanced timing
             injector.key(Key::Control, Direction::Press)
@@ -144,38 +273,10 @@ fn inject_text_via_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error
                 })
         };
         
-        match clipboard_result {
-            Ok(_) => {
-                info!("Balanced paste successful");
-                // Balanced wait time for paste completion
-                thread::sleep(Duration::from_millis(100)); // Balanced for Windows
-            }
-            Err(e) => {
-                warn!("Paste failed: {}, trying balanced character-by-character method", e);
-                
-                // Fallback: type character by character with balanced timing
-                info!("Using balanced character-by-character fallback for: '{}'", text);
-                for ch in text.chars() {
-                    match ch {
-                        '\n' => {
-                            injector.key(Key::Return, Direction::Click)
-                                .map_err(|e| format!("Failed to inject newline: {}", e))?;
-                        }
-                        '\t' => {
-                            injector.key(Key::Tab, Direction::Click)
-                                .map_err(|e| format!("Failed to inject tab: {}", e))?;
-                        }
-                        _ => {
-                            let char_str = ch.to_string();
-                            injector.text(&char_str)
-                                .map_err(|e| format!("Failed to inject character '{}': {}", ch, e))?;
-                        }
-                    }
-                    // Balanced delay between characters
-                    thread::sleep(Duration::from_millis(10)); // Balanced for Windows
-                }
-                info!("Balanced character-by-character injection completed");
-            }
+        if clipboard_result.is_ok() {
+            info!("Balanced paste successful");
+            // Balanced wait time for paste completion
+            thread::sleep(Duration::from_millis(100)); // Balanced for Windows
         }
         
         // Restore original clipboard content
@@ -185,6 +286,7 @@ fn inject_text_via_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error
             }
         }
         
+        clipboard_result.map_err(|e| format!("Paste failed: {}", e))?;
         info!("Balanced clipboard-based text injection completed successfully");
         Ok(())
     } else {
@@ -192,9 +294,14 @@ fn inject_text_via_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error
     }
 }
 
-// macOS-only helper that injects text without touching the clipboard. This avoids
-// crashes related to NSPasteboard access off the main thread.
-#[cfg(target_os = "macos")]
+#[cfg(not(target_os = "windows"))]
+fn inject_text_via_clipboard(_text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("Clipboard injection is only supported on Windows".into())
+}
+
+// Types the text without touching the clipboard. On macOS this also avoids crashes
+// related to NSPasteboard access off the main thread.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
 fn inject_text_char_by_character(text: &str) -> Result<(), Box<dyn std::error::Error>> {
     use enigo::{Key, Direction};
 
@@ -220,10 +327,10 @@ fn inject_text_char_by_character(text: &str) -> Result<(), Box<dyn std::error::E
             }
 
             // Small balanced delay so we don't overwhelm the target app.
-            std::thread::sleep(std::time::Duration::from_millis(6));
+            std::thread::sleep(TYPING_DELAY);
         }
 
-        info!("Character-by-character injection completed successfully on macOS");
+        info!("Character-by-character injection completed successfully on {}", std::env::consts::OS);
         Ok(())
     } else {
         Err("Text injector not initialised".into())
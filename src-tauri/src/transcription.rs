@@ -1,9 +1,96 @@
 use std::fs::File;
-use std::io::Read;
-use reqwest::blocking::Client;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use crate::network::{self, NetworkConfig};
 use serde_json::Value;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
+use crate::history::Segment;
+use crate::local_whisper;
+
+pub const DEFAULT_MODEL: &str = "whisper-large-v3-turbo";
+// The turbo model cannot translate, so translation requests fall back to this one.
+pub const TRANSLATION_MODEL: &str = "whisper-large-v3";
+// Speech-to-text models Groq serves.
+pub const MODELS: &[&str] = &["whisper-large-v3-turbo", "whisper-large-v3", "distil-whisper-large-v3-en"];
+pub const DEFAULT_BASE_URL: &str = "https://api.groq.com/openai/v1";
+const MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+pub const CANCELLED_ERROR: &str = "Transcription cancelled";
+
+// Bumped by cancel_in_flight; requests started under an older generation are abandoned.
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub fn cancel_in_flight() {
+    CANCEL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn current_generation() -> u64 {
+    CANCEL_GENERATION.load(Ordering::SeqCst)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TranscriptionBackend {
+    // Groq, or whichever OpenAI-compatible server the base URL points at.
+    #[default]
+    Groq,
+    // whisper.cpp on this machine, for when there's no network or API key.
+    Local,
+}
+
+#[derive(Clone, Debug)]
+pub struct TranscriptionOptions {
+    pub model: String,
+    pub language: Option<String>,
+    // Translate the speech to English instead of transcribing it as spoken.
+    pub translate: bool,
+    // Request verbose_json so the response carries timestamped segments.
+    pub segments: bool,
+    // Also ask for word timestamps; only takes effect together with `segments`.
+    pub words: bool,
+    // Context that steers spelling and style, e.g. names or jargon the speaker uses.
+    pub prompt: Option<String>,
+    pub network: NetworkConfig,
+    // From current_generation() when the job started, so a later cancel can be detected.
+    pub generation: u64,
+    // Root of an OpenAI-compatible API, without the /audio/... path.
+    pub base_url: String,
+    pub backend: TranscriptionBackend,
+    // ggml model file used by the local backend.
+    pub local_model_path: Option<PathBuf>,
+}
+
+impl TranscriptionOptions {
+    pub fn is_cancelled(&self) -> bool {
+        current_generation() != self.generation
+    }
+}
+
+// English-only models transcribe everything as English, so a language hint does nothing.
+pub fn is_english_only(model: &str) -> bool {
+    model.ends_with("-en")
+}
+
+impl Default for TranscriptionOptions {
+    fn default() -> Self {
+        Self {
+            model: DEFAULT_MODEL.to_string(),
+            language: None,
+            translate: false,
+            segments: false,
+            words: false,
+            prompt: None,
+            network: NetworkConfig::default(),
+            generation: current_generation(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            backend: TranscriptionBackend::default(),
+            local_model_path: None,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TranscriptionResult {
@@ -11,98 +98,260 @@ pub struct TranscriptionResult {
     pub round_trip_ms: u64,
     pub status: String,
     pub error: Option<String>,
+    pub segments: Option<Vec<Segment>>,
+    #[serde(default)]
+    pub words: Option<Vec<Segment>>,
+    // Language Groq detected, by name (e.g. "german"), when none was requested.
+    #[serde(default)]
+    pub language: Option<String>,
+    // Set when Groq rate limited the request, with the delay it asked for.
+    #[serde(skip)]
+    pub retry_after: Option<Duration>,
+    // Set for dropped connections and 5xx responses, which are worth retrying.
+    #[serde(skip)]
+    pub transient: bool,
 }
 
-pub fn transcribe_audio(file_path: &str, api_key: &str) -> TranscriptionResult {
-    let mut file = match File::open(file_path) {
-        Ok(f) => f,
-        Err(e) => {
-            return TranscriptionResult {
-                text: String::new(),
-                round_trip_ms: 0,
-                status: "error".to_string(),
-                error: Some(e.to_string()),
-            }
-        }
-    };
-    let mut buffer = Vec::new();
-    if let Err(e) = file.read_to_end(&mut buffer) {
-        return TranscriptionResult {
+impl TranscriptionResult {
+    pub fn error(round_trip_ms: u64, error: String) -> Self {
+        Self {
             text: String::new(),
-            round_trip_ms: 0,
+            round_trip_ms,
             status: "error".to_string(),
-            error: Some(e.to_string()),
+            error: Some(error),
+            segments: None,
+            words: None,
+            language: None,
+            retry_after: None,
+            transient: false,
         }
     }
 
-    let client = Client::new();
-    let url = "https://api.groq.com/openai/v1/audio/transcriptions";
+    fn transient(round_trip_ms: u64, error: String) -> Self {
+        Self {
+            transient: true,
+            ..Self::error(round_trip_ms, error)
+        }
+    }
+}
+
+// Used when a 429 carries no usable Retry-After header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(2);
+
+// Only the delay-seconds form is handled; Groq doesn't send HTTP dates here.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Duration {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+        .unwrap_or(DEFAULT_RETRY_AFTER)
+}
+
+// Pulls the human-readable message out of the error bodies Groq and OpenAI-compatible
+// backends send, falling back to the raw body and then to the bare status.
+fn api_error_message(status: reqwest::StatusCode, body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body).ok().and_then(|v| {
+        v["error"]["message"]
+            .as_str()
+            .or_else(|| v["error"].as_str())
+            .or_else(|| v["message"].as_str())
+            .map(str::to_string)
+    });
+    match message.or_else(|| Some(body.trim().to_string()).filter(|b| !b.is_empty())) {
+        Some(message) => format!("Groq API error: {} - {}", status, message),
+        None => format!("Groq API error: {}", status),
+    }
+}
+
+// Groq sometimes pads the text with a leading space or trailing newline; internal
+// newlines are kept as dictated.
+fn response_text(v: &Value) -> String {
+    v["text"].as_str().unwrap_or("").trim().to_string()
+}
+
+// Returns None when the backend ignored verbose_json and sent plain JSON instead.
+fn parse_segments(v: &Value) -> Option<Vec<Segment>> {
+    let segments = v["segments"].as_array()?;
+    Some(
+        segments
+            .iter()
+            .map(|segment| Segment {
+                start: segment["start"].as_f64().unwrap_or(0.0),
+                end: segment["end"].as_f64().unwrap_or(0.0),
+                text: segment["text"].as_str().unwrap_or("").trim().to_string(),
+            })
+            .collect(),
+    )
+}
+
+fn parse_words(v: &Value) -> Option<Vec<Segment>> {
+    let words = v["words"].as_array()?;
+    Some(
+        words
+            .iter()
+            .map(|word| Segment {
+                start: word["start"].as_f64().unwrap_or(0.0),
+                end: word["end"].as_f64().unwrap_or(0.0),
+                text: word["word"].as_str().unwrap_or("").trim().to_string(),
+            })
+            .collect(),
+    )
+}
+
+// Verifies the key by listing models, which is free and needs no audio.
+pub fn check_api_key(api_key: &str, network: &NetworkConfig) -> Result<(), String> {
+    let resp = network::client(network)?
+        .get(MODELS_URL)
+        .bearer_auth(api_key)
+        .send()
+        .map_err(|e| format!("Failed to reach Groq: {}", e))?;
+    let status = resp.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(api_error_message(status, &resp.text().unwrap_or_default()))
+    }
+}
+
+// The blocking client has no way to abort a request, so it is sent from its own thread and
+// abandoned if the job is cancelled before the response arrives. Returns None when cancelled.
+fn send_cancellable(
+    request: reqwest::blocking::RequestBuilder,
+    options: &TranscriptionOptions,
+) -> Option<reqwest::Result<reqwest::blocking::Response>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(request.send());
+    });
+    loop {
+        match rx.recv_timeout(CANCEL_POLL_INTERVAL) {
+            Ok(resp) => return Some(resp),
+            Err(RecvTimeoutError::Timeout) if options.is_cancelled() => return None,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
+pub fn transcribe_audio(file_path: &str, api_key: &str, options: &TranscriptionOptions) -> TranscriptionResult {
+    if options.backend == TranscriptionBackend::Local {
+        return local_whisper::transcribe(Path::new(file_path), options);
+    }
+    // The recording is already streamed to disk while capturing and the multipart form
+    // streams it from there, so long recordings are never held in memory as a whole.
+    if let Err(e) = File::open(file_path) {
+        return TranscriptionResult::error(0, e.to_string());
+    }
+
+    let client = match network::client(&options.network) {
+        Ok(client) => client,
+        Err(e) => return TranscriptionResult::error(0, e),
+    };
+    let (endpoint, model) = if options.translate {
+        let model = if options.model == DEFAULT_MODEL { TRANSLATION_MODEL } else { options.model.as_str() };
+        ("translations", model)
+    } else {
+        ("transcriptions", options.model.as_str())
+    };
+    let url = format!("{}/audio/{}", options.base_url.trim_end_matches('/'), endpoint);
+    let english_only = is_english_only(model);
+    let language = match &options.language {
+        Some(language) if english_only => {
+            if language != "en" {
+                eprintln!("{} only transcribes English, ignoring language '{}'", model, language);
+            }
+            None
+        }
+        // The translations endpoint always outputs English and rejects a language hint.
+        Some(_) if options.translate => None,
+        language => language.clone(),
+    };
     let form = match reqwest::blocking::multipart::Form::new()
         .file("file", file_path)
     {
-        Ok(f) => f.text("model", "whisper-large-v3-turbo").text("response_format", "json"),
-        Err(e) => {
-            return TranscriptionResult {
-                text: String::new(),
-                round_trip_ms: 0,
-                status: "error".to_string(),
-                error: Some(e.to_string()),
+        Ok(f) => {
+            // Without a language hint Groq detects one, and only verbose_json reports which.
+            let verbose = options.segments || (options.language.is_none() && !english_only);
+            let response_format = if verbose { "verbose_json" } else { "json" };
+            let form = f.text("model", model.to_string()).text("response_format", response_format);
+            let form = match &options.prompt {
+                Some(prompt) => form.text("prompt", prompt.clone()),
+                None => form,
+            };
+            // Asking for words alone would drop the segments from the response.
+            let form = if options.segments && options.words {
+                form.text("timestamp_granularities[]", "segment")
+                    .text("timestamp_granularities[]", "word")
+            } else {
+                form
+            };
+            match language {
+                Some(language) => form.text("language", language),
+                None => form,
             }
         }
+        Err(e) => return TranscriptionResult::error(0, e.to_string()),
     };
 
     let start = Instant::now();
-    let resp = client.post(url)
-        .multipart(form)
-        .bearer_auth(api_key)
-        .send();
+    let request = client.post(url).multipart(form);
+    let request = if api_key.is_empty() { request } else { request.bearer_auth(api_key) };
+    let Some(resp) = send_cancellable(request, options) else {
+        return TranscriptionResult::error(start.elapsed().as_millis() as u64, CANCELLED_ERROR.to_string());
+    };
     let duration = start.elapsed();
     let round_trip_ms = duration.as_millis() as u64;
     match resp {
         Ok(resp) => {
             let status = resp.status();
+            let retry_after = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                Some(parse_retry_after(resp.headers()))
+            } else {
+                None
+            };
             let text = match resp.text() {
                 Ok(t) => t,
-                Err(e) => {
-                    return TranscriptionResult {
-                        text: String::new(),
-                        round_trip_ms,
-                        status: "error".to_string(),
-                        error: Some(e.to_string()),
-                    }
-                }
+                Err(e) => return TranscriptionResult::transient(round_trip_ms, e.to_string()),
             };
             if !status.is_success() {
-                return TranscriptionResult {
-                    text: String::new(),
-                    round_trip_ms,
-                    status: "error".to_string(),
-                    error: Some(format!("Groq API error: {} - {}", status, text)),
-                }
+                let mut result = TranscriptionResult::error(round_trip_ms, api_error_message(status, &text));
+                result.retry_after = retry_after;
+                result.transient = status.is_server_error();
+                return result;
             }
             let v: Value = match serde_json::from_str(&text) {
                 Ok(val) => val,
-                Err(e) => {
-                    return TranscriptionResult {
-                        text: String::new(),
-                        round_trip_ms,
-                        status: "error".to_string(),
-                        error: Some(e.to_string()),
-                    }
-                }
+                Err(e) => return TranscriptionResult::error(round_trip_ms, e.to_string()),
             };
             TranscriptionResult {
-                text: v["text"].as_str().unwrap_or("").to_string(),
+                text: response_text(&v),
                 round_trip_ms,
                 status: "success".to_string(),
                 error: None,
+                segments: if options.segments { parse_segments(&v) } else { None },
+                words: if options.segments && options.words { parse_words(&v) } else { None },
+                language: v["language"].as_str().filter(|l| !l.is_empty()).map(str::to_string),
+                retry_after: None,
+                transient: false,
             }
         }
-        Err(e) => TranscriptionResult {
-            text: String::new(),
-            round_trip_ms,
-            status: "error".to_string(),
-            error: Some(e.to_string()),
-        },
+        Err(e) => TranscriptionResult::transient(round_trip_ms, e.to_string()),
+    }
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_text_is_trimmed() {
+        assert_eq!(response_text(&serde_json::json!({ "text": " hello world\n" })), "hello world");
+    }
+
+    #[test]
+    fn response_text_keeps_internal_newlines() {
+        assert_eq!(response_text(&serde_json::json!({ "text": " first line\nsecond line\n" })), "first line\nsecond line");
     }
-} 
\ No newline at end of file
+}
@@ -1,36 +1,133 @@
-use tauri::{AppHandle, Runtime};
+use tauri::{AppHandle, Runtime, WebviewUrl, WebviewWindowBuilder};
 use tauri::Manager;
-use tauri::tray::{TrayIcon, TrayIconBuilder};
-use tauri::menu::{MenuBuilder, MenuItemBuilder};
+use tauri::tray::{TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::menu::{Menu, MenuBuilder, MenuItemBuilder, SubmenuBuilder};
+use crate::history::History;
 
-pub fn create_system_tray<R: Runtime>(app: &AppHandle<R>) -> TrayIcon<R> {
+const TRAY_ID: &str = "main";
+const RECENT_COUNT: usize = 5;
+const RECENT_LABEL_CHARS: usize = 40;
+const RECENT_ID_PREFIX: &str = "recent:";
+
+// Single-line, truncated label for a transcript in the recent submenu.
+fn recent_label(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    crate::history::preview(&line, RECENT_LABEL_CHARS)
+}
+
+// Raises the dashboard above other windows, recreating it if it was destroyed.
+pub fn show_dashboard<R: Runtime>(app: &AppHandle<R>) {
+    let window = match app.get_webview_window("dashboard") {
+        Some(window) => window,
+        None => match WebviewWindowBuilder::new(app, "dashboard", WebviewUrl::App("dashboard.html".into()))
+            .title("VWisper Dashboard")
+            .inner_size(1200.0, 800.0)
+            .center()
+            .build()
+        {
+            Ok(window) => window,
+            Err(e) => {
+                eprintln!("Failed to create dashboard window: {}", e);
+                return;
+            }
+        },
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    // Windows refuses to foreground a window on set_focus alone when another app is active.
+    #[cfg(target_os = "windows")]
+    {
+        let _ = window.set_always_on_top(true);
+        let _ = window.set_always_on_top(false);
+    }
+    let _ = window.set_focus();
+}
+
+fn build_menu<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<Menu<R>> {
     let dashboard_item = MenuItemBuilder::new("Dashboard")
         .id("dashboard")
-        .build(app)
-        .unwrap();
+        .build(app)?;
     let quit_item = MenuItemBuilder::new("Quit")
         .id("quit")
-        .build(app)
-        .unwrap();
-    let menu = MenuBuilder::new(app)
+        .build(app)?;
+
+    let history = crate::HISTORY.get_or_init(History::new);
+    let recent: Vec<_> = history
+        .get_recent_entries(RECENT_COUNT * 2)
+        .into_iter()
+        .filter(|entry| entry.status == "success" && !entry.text.is_empty())
+        .take(RECENT_COUNT)
+        .collect();
+    let mut recent_menu = SubmenuBuilder::new(app, "Recent Transcripts");
+    if recent.is_empty() {
+        recent_menu = recent_menu.item(&MenuItemBuilder::new("No transcripts yet").enabled(false).build(app)?);
+    }
+    for entry in &recent {
+        let item = MenuItemBuilder::new(recent_label(&entry.text))
+            .id(format!("{}{}", RECENT_ID_PREFIX, entry.id))
+            .build(app)?;
+        recent_menu = recent_menu.item(&item);
+    }
+
+    MenuBuilder::new(app)
         .item(&dashboard_item)
+        .item(&recent_menu.build()?)
         .separator()
         .item(&quit_item)
         .build()
-        .unwrap();
-    TrayIconBuilder::new()
+}
+
+// Rebuilds the tray menu so the recent submenu reflects the latest history.
+pub fn refresh_tray_menu<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        match build_menu(app) {
+            Ok(menu) => {
+                let _ = tray.set_menu(Some(menu));
+            }
+            Err(e) => eprintln!("Failed to rebuild tray menu: {}", e),
+        }
+    }
+}
+
+fn paste_recent(entry_id: &str) {
+    let history = crate::HISTORY.get_or_init(History::new);
+    let text = match history.get_entries().into_iter().find(|entry| entry.id == entry_id) {
+        Some(entry) => entry.text,
+        None => return,
+    };
+    // Give focus a moment to return to the previous app once the menu closes.
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let methods = crate::settings::get_settings()
+            .map(|settings| settings.injection_fallback_chain)
+            .unwrap_or_else(|_| crate::settings::default_injection_chain());
+        if let Err(e) = crate::textinjection::inject_text(&text, &methods) {
+            eprintln!("Failed to paste recent transcript: {}", e);
+        }
+    });
+}
+
+pub fn create_system_tray<R: Runtime>(app: &AppHandle<R>) -> TrayIcon<R> {
+    let menu = build_menu(app).unwrap();
+    TrayIconBuilder::with_id(TRAY_ID)
         .icon(app.default_window_icon().unwrap().clone())
         .menu(&menu)
         .on_menu_event(|app, event| {
             if event.id.0 == "dashboard" {
-                if let Some(window) = app.get_webview_window("dashboard") {
-                    let _ = window.show();
-                    let _ = window.set_focus();
-                }
+                show_dashboard(app);
             } else if event.id.0 == "quit" {
-                std::process::exit(0);
+                // Shutdown waits on in-flight work, so keep it off the event loop thread.
+                let app = app.clone();
+                std::thread::spawn(move || crate::shutdown(&app));
+            } else if let Some(entry_id) = event.id.0.strip_prefix(RECENT_ID_PREFIX) {
+                paste_recent(entry_id);
+            }
+        })
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { .. } = event {
+                refresh_tray_menu(tray.app_handle());
             }
         })
         .build(app)
         .unwrap()
-} 
\ No newline at end of file
+}
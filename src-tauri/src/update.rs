@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::process::Command;
 use std::env;
-use reqwest::blocking::Client;
+use crate::network::{self, NetworkConfig};
 use std::io::Write;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -100,10 +100,10 @@ fn install_update(installer_path: &str) -> Result<(), String> {
     }
 }
 
-pub fn check_for_updates() -> Result<UpdateInfo, String> {
+pub fn check_for_updates(network: &NetworkConfig) -> Result<UpdateInfo, String> {
     let current_version = get_current_version()?;
     
-    let client = Client::new();
+    let client = network::client(network)?;
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
@@ -147,8 +147,8 @@ pub fn check_for_updates() -> Result<UpdateInfo, String> {
     })
 }
 
-pub fn download_and_install_update(download_url: String) -> Result<UpdateResult, String> {
-    let client = Client::new();
+pub fn download_and_install_update(download_url: String, network: &NetworkConfig) -> Result<UpdateResult, String> {
+    let client = network::client(network)?;
     
     let response = client
         .get(&download_url)
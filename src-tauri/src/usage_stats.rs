@@ -0,0 +1,361 @@
+use std::cmp::Reverse;
+use chrono::{Datelike, Local, Timelike};
+use serde::Serialize;
+use crate::history::{SessionOutcome, TranscriptionEntry};
+
+#[derive(Debug, Default, Serialize)]
+pub struct UsageStats {
+    pub total_recordings: usize,
+    // Sessions that produced text; empty transcripts are counted separately.
+    pub successful_recordings: usize,
+    pub empty_recordings: usize,
+    pub failed_recordings: usize,
+    pub total_duration_ms: u64,
+    pub timed_recordings: usize,
+    pub total_processing_ms: u64,
+    pub processed_recordings: usize,
+    pub total_speech_ratio: f64,
+    pub measured_recordings: usize,
+    pub mostly_silent_recordings: usize,
+    // Words and audio length of the timed sessions that produced text, for get_wpm.
+    pub dictated_words: usize,
+    pub dictated_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormattedUsageStats {
+    pub total_recordings: usize,
+    pub successful_recordings: usize,
+    pub empty_recordings: usize,
+    pub failed_recordings: usize,
+    pub total_duration_formatted: String,
+    pub average_duration_ms: f64,
+    pub average_processing_ms: f64,
+    pub success_rate: f64,
+    pub average_speech_ratio: f64,
+    pub mostly_silent_recordings: usize,
+    pub average_wpm: f64,
+}
+
+// Sessions with less speech than this are flagged as mostly dead air.
+const MOSTLY_SILENT_RATIO: f32 = 0.3;
+
+// Average typing speed used to estimate how much time dictation saved.
+const TYPING_WPM: f64 = 40.0;
+// Number of most recent sessions included in the rolling WPM average.
+const ROLLING_WINDOW: usize = 20;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ProductivityStats {
+    pub total_words: usize,
+    pub total_characters: usize,
+    pub average_wpm: f64,
+    pub rolling_average_wpm: f64,
+    pub estimated_time_saved_ms: u64,
+    // Sessions started in each hour of the local day.
+    pub hourly_sessions: [usize; 24],
+    pub peak_usage_hour: Option<u32>,
+    // Weekday with the most sessions, e.g. "Monday".
+    pub most_active_day: Option<String>,
+}
+
+const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+// Index of the largest count, the earliest on ties; None when everything is zero.
+fn busiest(counts: &[usize]) -> Option<usize> {
+    let (index, &count) = counts.iter().enumerate().max_by_key(|&(index, &count)| (count, Reverse(index)))?;
+    (count > 0).then_some(index)
+}
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+// Words per minute for a session, or None for failed, empty or untimed sessions.
+pub fn entry_wpm(entry: &TranscriptionEntry) -> Option<f64> {
+    if entry.status != "success" {
+        return None;
+    }
+    let words = word_count(&entry.text);
+    let duration = entry_duration_ms(entry)?;
+    if words == 0 || duration == 0 {
+        return None;
+    }
+    Some(words as f64 / (duration as f64 / 60_000.0))
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+impl ProductivityStats {
+    pub fn from_entries(entries: &[TranscriptionEntry]) -> Self {
+        let mut stats = ProductivityStats::default();
+        let mut rates = Vec::new();
+        let mut dictated_ms = 0u64;
+        let mut timed_words = 0usize;
+        for entry in entries.iter().filter(|e| e.status == "success") {
+            let words = word_count(&entry.text);
+            stats.total_words += words;
+            stats.total_characters += entry.text.chars().count();
+            if let Some(wpm) = entry_wpm(entry) {
+                rates.push(wpm);
+                timed_words += words;
+                dictated_ms += entry_duration_ms(entry).unwrap_or(0);
+            }
+        }
+        stats.average_wpm = mean(&rates);
+        stats.rolling_average_wpm = mean(&rates[rates.len().saturating_sub(ROLLING_WINDOW)..]);
+
+        let typing_ms = timed_words as f64 / TYPING_WPM * 60_000.0;
+        stats.estimated_time_saved_ms = (typing_ms - dictated_ms as f64).max(0.0) as u64;
+
+        let mut weekday_sessions = [0usize; 7];
+        for entry in entries {
+            let local = entry.timestamp.with_timezone(&Local);
+            stats.hourly_sessions[local.hour() as usize] += 1;
+            weekday_sessions[local.weekday().num_days_from_monday() as usize] += 1;
+        }
+        stats.peak_usage_hour = busiest(&stats.hourly_sessions).map(|hour| hour as u32);
+        stats.most_active_day = busiest(&weekday_sessions).map(|day| WEEKDAYS[day].to_string());
+        stats
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CostEstimate {
+    pub month: String,
+    pub minutes_used: f64,
+    pub price_per_minute: f64,
+    pub estimated_cost: f64,
+}
+
+// Sums the audio sent for transcription during the current local month.
+pub fn estimate_month_cost(entries: &[TranscriptionEntry], price_per_minute: f64) -> CostEstimate {
+    let now = Local::now();
+    let total_ms: u64 = entries
+        .iter()
+        .filter(|entry| {
+            let local = entry.timestamp.with_timezone(&Local);
+            local.year() == now.year() && local.month() == now.month()
+        })
+        .filter_map(entry_duration_ms)
+        .sum();
+    let minutes_used = total_ms as f64 / 60_000.0;
+    CostEstimate {
+        month: now.format("%Y-%m").to_string(),
+        minutes_used,
+        price_per_minute,
+        estimated_cost: minutes_used * price_per_minute,
+    }
+}
+
+// Prefer the recorded wall-clock length, then the sample-derived one, then how long the
+// hotkey was held.
+pub fn entry_duration_ms(entry: &TranscriptionEntry) -> Option<u64> {
+    entry
+        .audio_stats
+        .as_ref()
+        .map(|stats| stats.wall_clock_ms.unwrap_or(stats.duration_ms))
+        .or(entry.hold_time_ms)
+}
+
+impl UsageStats {
+    pub fn from_entries(entries: &[TranscriptionEntry]) -> Self {
+        let mut stats = UsageStats::default();
+        for entry in entries {
+            stats.total_recordings += 1;
+            match entry.outcome() {
+                SessionOutcome::Text => stats.successful_recordings += 1,
+                SessionOutcome::Empty => stats.empty_recordings += 1,
+                SessionOutcome::Failed => stats.failed_recordings += 1,
+            }
+            if let Some(duration) = entry_duration_ms(entry) {
+                stats.total_duration_ms += duration;
+                stats.timed_recordings += 1;
+                if entry.outcome() == SessionOutcome::Text {
+                    stats.dictated_words += word_count(&entry.text);
+                    stats.dictated_ms += duration;
+                }
+            }
+            if let Some(round_trip) = entry.round_trip_ms {
+                stats.total_processing_ms += round_trip;
+                stats.processed_recordings += 1;
+            }
+            if let Some(ratio) = entry.audio_stats.as_ref().and_then(|a| a.speech_ratio) {
+                stats.total_speech_ratio += ratio as f64;
+                stats.measured_recordings += 1;
+                if ratio < MOSTLY_SILENT_RATIO {
+                    stats.mostly_silent_recordings += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    pub fn get_average_speech_ratio(&self) -> f64 {
+        if self.measured_recordings == 0 {
+            return 0.0;
+        }
+        self.total_speech_ratio / self.measured_recordings as f64
+    }
+
+    pub fn get_total_duration_formatted(&self) -> String {
+        let total_secs = self.total_duration_ms / 1000;
+        let hours = total_secs / 3600;
+        let minutes = (total_secs % 3600) / 60;
+        let seconds = total_secs % 60;
+        if hours > 0 {
+            format!("{}h {}m", hours, minutes)
+        } else if minutes > 0 {
+            format!("{}m {}s", minutes, seconds)
+        } else {
+            format!("{}s", seconds)
+        }
+    }
+
+    pub fn get_average_duration(&self) -> f64 {
+        if self.timed_recordings == 0 {
+            return 0.0;
+        }
+        self.total_duration_ms as f64 / self.timed_recordings as f64
+    }
+
+    pub fn get_average_processing_time(&self) -> f64 {
+        if self.processed_recordings == 0 {
+            return 0.0;
+        }
+        self.total_processing_ms as f64 / self.processed_recordings as f64
+    }
+
+    // Overall speaking rate across successful sessions, rather than an average of each
+    // session's rate, so short clips don't skew it.
+    pub fn get_wpm(&self) -> f64 {
+        if self.dictated_ms == 0 {
+            return 0.0;
+        }
+        self.dictated_words as f64 / (self.dictated_ms as f64 / 60_000.0)
+    }
+
+    // Share of sessions that produced text.
+    pub fn get_success_rate(&self) -> f64 {
+        if self.total_recordings == 0 {
+            return 0.0;
+        }
+        self.successful_recordings as f64 / self.total_recordings as f64 * 100.0
+    }
+
+    pub fn formatted(&self) -> FormattedUsageStats {
+        FormattedUsageStats {
+            total_recordings: self.total_recordings,
+            successful_recordings: self.successful_recordings,
+            empty_recordings: self.empty_recordings,
+            failed_recordings: self.failed_recordings,
+            total_duration_formatted: self.get_total_duration_formatted(),
+            average_duration_ms: self.get_average_duration(),
+            average_processing_ms: self.get_average_processing_time(),
+            success_rate: self.get_success_rate(),
+            average_speech_ratio: self.get_average_speech_ratio(),
+            mostly_silent_recordings: self.mostly_silent_recordings,
+            average_wpm: self.get_wpm(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn entry(timestamp: DateTime<Utc>, text: &str) -> TranscriptionEntry {
+        TranscriptionEntry {
+            id: timestamp.to_rfc3339(),
+            timestamp,
+            text: text.to_string(),
+            round_trip_ms: None,
+            hold_time_ms: None,
+            status: "success".to_string(),
+            wav_path: None,
+            audio_stats: None,
+            segments: None,
+            words: None,
+            error: None,
+        }
+    }
+
+    // Hour and weekday come from local time, so build timestamps from it as well.
+    // 2026-03-02 is a Monday.
+    fn local(day: u32, hour: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(2026, 3, day, hour, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn no_recordings_give_zeroes() {
+        let stats = UsageStats::from_entries(&[]);
+        assert_eq!(stats.get_average_duration(), 0.0);
+        assert_eq!(stats.get_average_processing_time(), 0.0);
+        assert_eq!(stats.get_success_rate(), 0.0);
+        assert_eq!(stats.get_average_speech_ratio(), 0.0);
+        assert_eq!(stats.get_wpm(), 0.0);
+        assert_eq!(stats.get_total_duration_formatted(), "0s");
+    }
+
+    #[test]
+    fn untimed_recordings_give_zero_averages() {
+        let stats = UsageStats::from_entries(&[entry(Utc::now(), "hello")]);
+        assert_eq!(stats.total_recordings, 1);
+        assert_eq!(stats.get_average_duration(), 0.0);
+        assert_eq!(stats.get_average_processing_time(), 0.0);
+        assert_eq!(stats.get_success_rate(), 100.0);
+        assert_eq!(stats.get_total_duration_formatted(), "0s");
+    }
+
+    #[test]
+    fn busiest_prefers_the_earliest_of_tied_counts() {
+        assert_eq!(busiest(&[1, 3, 0, 3]), Some(1));
+        assert_eq!(busiest(&[0, 0, 2]), Some(2));
+    }
+
+    #[test]
+    fn busiest_is_none_when_all_counts_are_zero() {
+        assert_eq!(busiest(&[0; 24]), None);
+        assert_eq!(busiest(&[]), None);
+    }
+
+    #[test]
+    fn peak_hour_and_day_come_from_local_time() {
+        let entries = [
+            entry(local(2, 9), "monday morning"),
+            entry(local(4, 14), "wednesday afternoon"),
+            entry(local(4, 14), "wednesday again"),
+            entry(local(5, 9), "thursday morning"),
+            entry(local(4, 20), "wednesday evening"),
+        ];
+        let stats = ProductivityStats::from_entries(&entries);
+        assert_eq!(stats.hourly_sessions[9], 2);
+        assert_eq!(stats.hourly_sessions[14], 2);
+        assert_eq!(stats.hourly_sessions[20], 1);
+        // 9:00 and 14:00 are tied, so the earlier hour wins.
+        assert_eq!(stats.peak_usage_hour, Some(9));
+        assert_eq!(stats.most_active_day.as_deref(), Some("Wednesday"));
+    }
+
+    #[test]
+    fn tied_weekdays_pick_the_earliest_in_the_week() {
+        let entries = [entry(local(6, 10), "friday"), entry(local(3, 11), "tuesday")];
+        let stats = ProductivityStats::from_entries(&entries);
+        assert_eq!(stats.most_active_day.as_deref(), Some("Tuesday"));
+        assert_eq!(stats.peak_usage_hour, Some(10));
+    }
+
+    #[test]
+    fn no_sessions_have_no_peak_hour_or_day() {
+        let stats = ProductivityStats::from_entries(&[]);
+        assert_eq!(stats.hourly_sessions, [0; 24]);
+        assert_eq!(stats.peak_usage_hour, None);
+        assert_eq!(stats.most_active_day, None);
+    }
+}
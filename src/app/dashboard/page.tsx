@@ -1,5 +1,6 @@
 import { useEffect, useState, useMemo, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { SiteHeader } from "@/components/dashboard/sheader";
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from "@/components/ui/card";
 import { Separator } from "@/components/ui/separator";
@@ -12,6 +13,8 @@ import { format } from "date-fns";
 export default function DashboardPage() {
   const [history, setHistory] = useState<any[]>([]);
   const [loading, setLoading] = useState(true);
+  const [askTelemetry, setAskTelemetry] = useState(false);
+  const [audioStatus, setAudioStatus] = useState("");
 
   const fetchHistory = useCallback(async () => {
     try {
@@ -50,6 +53,35 @@ export default function DashboardPage() {
     fetchHistory();
   }, [fetchHistory]);
 
+  useEffect(() => {
+    invoke("get_telemetry_status").then((status: any) => {
+      setAskTelemetry(!status.consent_asked);
+    });
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<any>("settings-updated", (event) => {
+      setAskTelemetry(!event.payload.telemetry_consent_asked);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const rescanAudio = async () => {
+    try {
+      const info: any = await invoke("reinit_audio");
+      setAudioStatus(`${info.device} (${info.sample_rate} Hz, ${info.channels} ch)`);
+    } catch (error) {
+      setAudioStatus(String(error));
+    }
+  };
+
+  const answerTelemetry = async (enabled: boolean) => {
+    await invoke("set_telemetry_consent", { enabled });
+    setAskTelemetry(false);
+  };
+
   // Memoize expensive computations
   const stats = useMemo(() => {
     const totalTranscriptions = history.length;
@@ -120,9 +152,28 @@ export default function DashboardPage() {
             <div className="px-4 lg:px-6">
               <div className="flex items-center justify-between space-y-2">
                 <h2 className="text-3xl font-bold tracking-tight">VWisper Dashboard</h2>
+                <div className="flex items-center gap-2">
+                  {audioStatus && <span className="text-sm text-muted-foreground">{audioStatus}</span>}
+                  <Button variant="outline" onClick={rescanAudio}>Re-scan devices</Button>
+                </div>
               </div>
               <Separator className="my-4" />
 
+              {askTelemetry && (
+                <Card className="mb-8">
+                  <CardHeader>
+                    <CardTitle>Help improve VWisper?</CardTitle>
+                    <CardDescription>
+                      Send anonymous crash and error counts. Transcripts and your API key are never included. Takes effect after a restart.
+                    </CardDescription>
+                  </CardHeader>
+                  <CardContent className="flex gap-2">
+                    <Button onClick={() => answerTelemetry(true)}>Share</Button>
+                    <Button variant="outline" onClick={() => answerTelemetry(false)}>No thanks</Button>
+                  </CardContent>
+                </Card>
+              )}
+
               <div className="grid gap-6 md:grid-cols-2 lg:grid-cols-3 mb-8">
                 <Card>
                   <CardHeader>
@@ -13,6 +13,7 @@ export default function SettingsPage() {
   const [apiKey, setApiKey] = useState("");
   const [saveHistory, setSaveHistory] = useState(true);
   const [saveAudio, setSaveAudio] = useState(true);
+  const [historyMaxEntries, setHistoryMaxEntries] = useState(1000);
   const [autostartEnabled, setAutostartEnabled] = useState(false);
   const [loading, setLoading] = useState(true);
   const [saved, setSaved] = useState(false);
@@ -25,6 +26,7 @@ export default function SettingsPage() {
       setApiKey(settings.groq_api_key || "");
       setSaveHistory(settings.save_history !== false);
       setSaveAudio(settings.save_audio !== false);
+      setHistoryMaxEntries(settings.history_max_entries ?? 1000);
       setLoading(false);
     });
     invoke("get_settings_path").then((path) => {
@@ -36,7 +38,7 @@ export default function SettingsPage() {
 
   const handleSave = async () => {
     setSaved(false);
-    await invoke("save_settings", { groqApiKey: apiKey, saveHistory, saveAudio });
+    await invoke("save_settings", { groqApiKey: apiKey, saveHistory, saveAudio, historyMaxEntries });
     setSaved(true);
     setInputType("password");
   };
@@ -47,6 +49,7 @@ export default function SettingsPage() {
     setApiKey("");
     setSaveHistory(true);
     setSaveAudio(true);
+    setHistoryMaxEntries(1000);
     setSaved(true);
     setInputType("text");
   };
@@ -142,6 +145,23 @@ export default function SettingsPage() {
                         Save Audio Files
                       </Label>
                     </div>
+                    <div className="space-y-2">
+                      <Label htmlFor="history-max-entries">Maximum History Entries (0 = keep forever)</Label>
+                      <Input
+                        id="history-max-entries"
+                        type="number"
+                        min={0}
+                        value={historyMaxEntries}
+                        onChange={e => setHistoryMaxEntries(Math.max(0, parseInt(e.target.value, 10) || 0))}
+                        disabled={!saveHistory}
+                        className="max-w-xs"
+                      />
+                      {historyMaxEntries === 0 && (
+                        <div className="text-sm text-yellow-600">
+                          Unlimited history keeps every transcription, so the history file can grow large over time.
+                        </div>
+                      )}
+                    </div>
                   </div>
                 </CardContent>
               </Card>